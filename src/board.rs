@@ -7,6 +7,14 @@ use std::mem;
 pub const MAX_COLS: usize = 7;
 pub const MAX_ROWS: usize = 9;
 
+// a column this many rows or fewer from the phage puts the run in danger
+const DANGER_ROWS_LEFT: usize = 2;
+const DANGER_HEIGHT: usize = MAX_ROWS - DANGER_ROWS_LEFT;
+
+// score for each file a bomb pair would clear, divided by the moves it takes
+// to bring the bombs together
+const BOMB_FILE_VALUE: f64 = 2.0;
+
 #[derive(PartialEq, Eq, Copy, Clone, Hash)]
 pub enum Color {
     Red,
@@ -16,6 +24,8 @@ pub enum Color {
     Pink,
 }
 
+pub const COLORS: [Color; 5] = [Red, Yellow, Blue, Cyan, Pink];
+
 #[derive(PartialEq, Eq, Copy, Clone, Hash)]
 pub enum Item {
     File(Color),
@@ -36,22 +46,42 @@ impl Item {
         }
     }
 
-    pub fn to_matched(&self) -> Item {
+    pub fn to_matched(self) -> Item {
         match self {
-            File(c) => MatchedFile(*c),
-            Bomb(c) => MatchedBomb(*c),
-            MatchedFile(c) => MatchedFile(*c),
-            MatchedBomb(c) => MatchedBomb(*c),
+            File(c) => MatchedFile(c),
+            Bomb(c) => MatchedBomb(c),
+            MatchedFile(c) => MatchedFile(c),
+            MatchedBomb(c) => MatchedBomb(c),
             Empty => Empty,
         }
     }
 
-    pub fn to_normal(&self) -> Item {
+    // number of touching items of the same kind needed to make a match
+    pub fn match_size(self) -> usize {
+        match self {
+            File(_) | MatchedFile(_) => 4,
+            Bomb(_) | MatchedBomb(_) => 2,
+            Empty => panic!("Empty items cannot be matched"),
+        }
+    }
+
+    pub fn color(self) -> Option<Color> {
         match self {
-            File(c) => File(*c),
-            Bomb(c) => Bomb(*c),
-            MatchedFile(c) => File(*c),
-            MatchedBomb(c) => Bomb(*c),
+            File(c) | Bomb(c) | MatchedFile(c) | MatchedBomb(c) => Some(c),
+            Empty => None,
+        }
+    }
+
+    pub fn is_bomb(self) -> bool {
+        matches!(self, Bomb(_) | MatchedBomb(_))
+    }
+
+    pub fn to_normal(self) -> Item {
+        match self {
+            File(c) => File(c),
+            Bomb(c) => Bomb(c),
+            MatchedFile(c) => File(c),
+            MatchedBomb(c) => Bomb(c),
             Empty => Empty,
         }
     }
@@ -88,6 +118,24 @@ pub struct Board {
     blocks: [[Item; MAX_COLS]; MAX_ROWS],
 }
 
+#[derive(Copy, Clone, Debug)]
+pub struct Danger {
+    pub tallest_col: usize,
+    pub height: usize,
+    // rows expected to come in before a path for the board has been played
+    pub incoming: usize,
+}
+
+impl Danger {
+    pub fn rows_left(&self) -> usize {
+        MAX_ROWS.saturating_sub(self.height + self.incoming)
+    }
+
+    pub fn is_emergency(&self) -> bool {
+        self.height + self.incoming >= DANGER_HEIGHT
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum Move {
     Left,
@@ -121,6 +169,26 @@ impl Board {
         b
     }
 
+    pub fn phage_col(&self) -> usize {
+        self.phage_col
+    }
+
+    pub fn held(&self) -> Item {
+        self.held
+    }
+
+    pub fn item(&self, row: usize, col: usize) -> Item {
+        self.blocks[row][col]
+    }
+
+    // the board the game should show once the path has been played, with any
+    // resulting match outlined the way the screen shows it before clearing
+    pub fn predict(&self, path: &[Move]) -> Board {
+        let mut b = path.iter().fold(self.clone(), |b, m| b.do_move(*m));
+        b.mark_matches();
+        b
+    }
+
     fn move_left(&mut self) {
         if self.phage_col > 0 {
             self.phage_col -= 1;
@@ -172,6 +240,25 @@ impl Board {
         }
     }
 
+    // matched items stay on screen outlined until the clearing animation ends
+    pub fn is_clearing(&self) -> bool {
+        self.has_matched()
+    }
+
+    // the board once the clearing animation is over
+    pub fn cleared(&self) -> Board {
+        let mut b = self.clone();
+        for row in 0..MAX_ROWS {
+            for col in 0..MAX_COLS {
+                if b.blocks[row][col].is_matched() {
+                    b.blocks[row][col] = Empty;
+                }
+            }
+        }
+        b.settle_blocks();
+        b
+    }
+
     fn has_matched(&self) -> bool {
         for row in 0..MAX_ROWS {
             for col in 0..MAX_COLS {
@@ -206,7 +293,7 @@ impl Board {
         row: usize,
         col: usize,
         b: Item,
-        mut visited: &mut [[bool; MAX_COLS]; MAX_ROWS],
+        visited: &mut [[bool; MAX_COLS]; MAX_ROWS],
     ) -> usize {
         if visited[row][col] {
             return 0;
@@ -221,21 +308,65 @@ impl Board {
         let mut size = 1;
 
         if row > 0 {
-            size += self.group_size(row - 1, col, b, &mut visited);
+            size += self.group_size(row - 1, col, b, visited);
         }
         if col > 0 {
-            size += self.group_size(row, col - 1, b, &mut visited);
+            size += self.group_size(row, col - 1, b, visited);
         }
         if row < MAX_ROWS - 1 {
-            size += self.group_size(row + 1, col, b, &mut visited);
+            size += self.group_size(row + 1, col, b, visited);
         }
         if col < MAX_COLS - 1 {
-            size += self.group_size(row, col + 1, b, &mut visited);
+            size += self.group_size(row, col + 1, b, visited);
         }
 
         size
     }
 
+    fn mark_matches(&mut self) {
+        let mut visited = [[false; MAX_COLS]; MAX_ROWS];
+
+        for row in 0..MAX_ROWS {
+            for col in 0..MAX_COLS {
+                let b = self.blocks[row][col].to_normal();
+                if b == Empty || visited[row][col] {
+                    continue;
+                }
+
+                let mut group = [[false; MAX_COLS]; MAX_ROWS];
+                let group_size = self.group_size(row, col, b, &mut group);
+                let matched = group_size >= b.match_size();
+
+                for grow in 0..MAX_ROWS {
+                    for gcol in 0..MAX_COLS {
+                        if group[grow][gcol] {
+                            visited[grow][gcol] = true;
+                            if matched {
+                                self.blocks[grow][gcol] = b.to_matched();
+                            }
+                        }
+                    }
+                }
+
+                if matched && b.is_bomb() {
+                    self.mark_color(b.color().unwrap());
+                }
+            }
+        }
+    }
+
+    // a matched bomb takes every other block of its color with it
+    fn mark_color(&mut self, c: Color) {
+        for row in 0..MAX_ROWS {
+            for col in 0..MAX_COLS {
+                let b = self.blocks[row][col];
+                if b.color() == Some(c) {
+                    self.blocks[row][col] = b.to_matched();
+                }
+            }
+        }
+    }
+
     pub fn has_match(&self) -> bool {
         let mut visited: [[bool; MAX_COLS]; MAX_ROWS] = [[false; MAX_COLS]; MAX_ROWS];
 
@@ -244,15 +375,7 @@ impl Board {
                 let b = self.blocks[row][col];
                 if b != Empty && !b.is_matched() {
                     let group_size = self.group_size(row, col, b, &mut visited);
-                    let match_size = match b {
-                        File(_) => 4,
-                        Bomb(_) => 2,
-                        MatchedFile(_) => panic!("Should not start match search from matched item"),
-                        MatchedBomb(_) => panic!("Should not start match search from matched item"),
-                        Empty => panic!("Empty items cannot be matched"),
-                    };
-
-                    if group_size >= match_size {
+                    if group_size >= b.match_size() {
                         return true;
                     }
                 }
@@ -313,6 +436,36 @@ impl Board {
         heights
     }
 
+    pub fn danger(&self, incoming: usize) -> Danger {
+        let heights = self.column_heights();
+
+        let mut danger = Danger {
+            tallest_col: 0,
+            height: 0,
+            incoming,
+        };
+        for col in 0..MAX_COLS {
+            if heights[col] > danger.height {
+                danger.tallest_col = col;
+                danger.height = heights[col];
+            }
+        }
+        danger
+    }
+
+    // in an emergency only the columns past the danger line matter, so any
+    // move that brings them down beats building groups, counting the rows
+    // still to come in
+    pub fn emergency_score(&self, incoming: usize) -> f64 {
+        let overflow: usize = self
+            .column_heights()
+            .iter()
+            .map(|h| (h + incoming).saturating_sub(DANGER_HEIGHT - 1).pow(2))
+            .sum();
+
+        -(overflow as f64)
+    }
+
     // board imbalance is the sum of squares of differences from the mean column height
     fn imbalance(&self) -> f64 {
         let heights = self.column_heights();
@@ -323,6 +476,43 @@ impl Board {
         heights.iter().map(|h| (*h as f64 - mean).powi(2)).sum()
     }
 
+    // two bombs of a color close together are a clear of every file of that
+    // color waiting to happen, worth more the more files there are
+    fn bomb_setup(&self) -> f64 {
+        let mut files = [0; COLORS.len()];
+        let mut bombs = Vec::new();
+
+        for row in 0..MAX_ROWS {
+            for col in 0..MAX_COLS {
+                match self.blocks[row][col] {
+                    File(c) => files[c as usize] += 1,
+                    Bomb(c) => bombs.push((c, row, col)),
+                    _ => (),
+                }
+            }
+        }
+
+        let mut closest = [usize::MAX; COLORS.len()];
+        for (i, &(c, row, col)) in bombs.iter().enumerate() {
+            for &(oc, orow, ocol) in bombs[i + 1..].iter() {
+                if oc == c {
+                    let dist = row.max(orow) - row.min(orow) + col.max(ocol) - col.min(ocol);
+                    closest[c as usize] = closest[c as usize].min(dist);
+                }
+            }
+        }
+
+        let mut setup = 0.0;
+        for c in COLORS.iter() {
+            let dist = closest[*c as usize];
+            // adjacent bombs are already a match
+            if dist != usize::MAX && dist > 1 {
+                setup += files[*c as usize] as f64 * BOMB_FILE_VALUE / (dist - 1) as f64;
+            }
+        }
+        setup
+    }
+
     pub fn score(&self) -> f64 {
         let mut score = 0.0;
         let mut visited = [[false; MAX_COLS]; MAX_ROWS];
@@ -333,7 +523,10 @@ impl Board {
                     let b = self.blocks[row][col];
                     if b != Empty {
                         let group_size = self.group_size(row, col, b, &mut visited);
-                        score += (group_size.pow(2)) as f64;
+                        // bombs are valued by what they clear, see bomb_setup()
+                        if !b.is_bomb() {
+                            score += (group_size.pow(2)) as f64;
+                        }
                     }
                 }
             }
@@ -349,39 +542,45 @@ impl Board {
         // assert!(min <= max);
         // score -= ((max - min).pow(2)) as f64;
 
+        score += self.bomb_setup();
+
         score -= self.imbalance().powi(2);
         // score -= max as f64;
 
         score
     }
+}
 
-    pub fn print(&self) {
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for row in self.blocks[..].iter() {
-            print!("|");
+            write!(f, "|")?;
             for item in row {
-                print!("{}", item);
+                write!(f, "{}", item)?;
             }
-            println!("|");
+            writeln!(f, "|")?;
         }
 
-        println!(
+        writeln!(
+            f,
             "|{}^{}|",
             " ".repeat(self.phage_col),
             " ".repeat(MAX_COLS - self.phage_col - 1)
-        );
-        println!(
+        )?;
+        write!(
+            f,
             "|{}{}{}|",
             " ".repeat(self.phage_col),
             self.held,
             " ".repeat(MAX_COLS - self.phage_col - 1)
-        );
+        )
     }
 }
 
 pub fn make_board(phage_col: usize, held: Item, items: [[Item; MAX_COLS]; MAX_ROWS]) -> Board {
     let mut board = Board {
-        phage_col: phage_col,
-        held: held,
+        phage_col,
+        held,
         blocks: items,
     };
 
@@ -0,0 +1,56 @@
+use crate::board::{Danger, MAX_ROWS};
+use std::time::Duration;
+
+// used until the scroll speed is known
+const DEFAULT_SEARCH_TIME: Duration = Duration::from_millis(110);
+// moves in a typical path, reserved for playing it before the next row
+const TYPICAL_PATH_MOVES: u32 = 6;
+
+// decides how long to search each board
+pub struct Budget {
+    min: Duration,
+    max: Duration,
+    // time to send one move with the key timing, as calibrated against the
+    // game with --calibrate
+    move_time: Duration,
+}
+
+impl Budget {
+    pub fn new(min: Duration, max: Duration, move_time: Duration) -> Budget {
+        Budget {
+            min,
+            max,
+            move_time,
+        }
+    }
+
+    pub fn move_time(&self) -> Duration {
+        self.move_time
+    }
+
+    // a row coming in before a typical path has been played lands on the
+    // board before the bot can do anything about it
+    pub fn rows_incoming(&self, next_row: Option<Duration>) -> usize {
+        match next_row {
+            Some(next_row) if next_row < self.move_time * TYPICAL_PATH_MOVES => 1,
+            _ => 0,
+        }
+    }
+
+    // searches take whatever is left before the next row once a typical path
+    // has been played, less the closer the stack is to the phage, and nothing
+    // beyond the minimum in an emergency
+    pub fn for_board(&self, danger: Danger, next_row: Option<Duration>) -> Duration {
+        if danger.is_emergency() {
+            return self.min;
+        }
+
+        let available = match next_row {
+            Some(next_row) => next_row.saturating_sub(self.move_time * TYPICAL_PATH_MOVES),
+            None => DEFAULT_SEARCH_TIME,
+        };
+        let budget = available * danger.rows_left() as u32 / MAX_ROWS as u32;
+
+        budget.max(self.min).min(self.max)
+    }
+}
@@ -0,0 +1,172 @@
+use crate::board::{Move, MAX_COLS};
+use crate::capture::Capture;
+use crate::error::{Error, Result};
+use crate::screen;
+use crate::screen::{KeyTiming, Keys};
+use log::info;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::xproto::Window;
+
+// tried from slowest to fastest, stopping at the first that drops a move
+const CANDIDATE_DELAYS_MILLIS: [u64; 8] = [17, 14, 12, 10, 8, 6, 4, 2];
+const TRIALS: usize = 6;
+// a move the phage has not made by now was dropped
+const REACTION_TIMEOUT: Duration = Duration::from_millis(250);
+// a new frame comes every 16 ms, polling faster times reactions closely
+// without capturing the same frame over and over
+const POLL_TIME: Duration = Duration::from_millis(2);
+// a phage that stays unreadable this long is not on screen
+const FIND_TIMEOUT: Duration = Duration::from_millis(1000);
+// added to the fastest reliable delays to cover frame time jitter
+const SAFETY_MARGIN: Duration = Duration::from_millis(2);
+
+fn phage_col<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+) -> Result<Option<usize>> {
+    Ok(screen::find_phage_col(&screen::screenshot_game(
+        conn, capture, window,
+    )?))
+}
+
+// the phage's column, waiting out frames where it cannot be read
+fn find_phage<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+) -> Result<usize> {
+    let start = Instant::now();
+    loop {
+        if let Some(col) = phage_col(conn, capture, window)? {
+            return Ok(col);
+        }
+        if start.elapsed() > FIND_TIMEOUT {
+            return Err(Error::Calibration(
+                "the phage is not on screen, calibrate during a run",
+            ));
+        }
+        thread::sleep(POLL_TIME);
+    }
+}
+
+// sends the moves and waits for the phage to end up in the expected column,
+// returning how long that took
+fn trial<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+    keys: &Keys,
+    moves: usize,
+) -> Result<Option<Duration>> {
+    let before = find_phage(conn, capture, window)?;
+    let (m, expected) = if before >= moves {
+        (Move::Left, before - moves)
+    } else {
+        (Move::Right, before + moves)
+    };
+    if expected >= MAX_COLS {
+        return Ok(None);
+    }
+
+    let start = Instant::now();
+    for _ in 0..moves {
+        screen::send_move(conn, keys, m)?;
+    }
+
+    while start.elapsed() < REACTION_TIMEOUT {
+        // the phage sprite can be unreadable mid step, so only a column
+        // reading counts
+        if phage_col(conn, capture, window)? == Some(expected) {
+            return Ok(Some(start.elapsed()));
+        }
+        thread::sleep(POLL_TIME);
+    }
+    Ok(None)
+}
+
+// the shortest delay in CANDIDATE_DELAYS_MILLIS for which every trial lands,
+// with timing built from the delay by with_delay
+fn fastest_reliable<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+    keys: &mut Keys,
+    moves: usize,
+    with_delay: impl Fn(Duration) -> KeyTiming,
+) -> Result<Option<Duration>> {
+    let mut fastest = None;
+
+    for millis in CANDIDATE_DELAYS_MILLIS.iter() {
+        let delay = Duration::from_millis(*millis);
+        keys.timing = with_delay(delay);
+
+        let mut total = Duration::from_millis(0);
+        for _ in 0..TRIALS {
+            match trial(conn, capture, window, keys, moves)? {
+                Some(reaction) => total += reaction,
+                None => {
+                    info!("  {} ms: moves dropped", millis);
+                    return Ok(fastest);
+                }
+            }
+        }
+
+        info!(
+            "  {} ms: reliable, {} ms average reaction",
+            millis,
+            (total / TRIALS as u32).as_millis()
+        );
+        fastest = Some(delay);
+    }
+
+    Ok(fastest)
+}
+
+// finds the shortest key hold that always moves the phage, then the shortest
+// wait between keys that never merges two presses into one; needs a run in
+// progress since it watches the phage; keys keep their timing afterwards
+pub fn calibrate<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+    keys: &mut Keys,
+) -> Result<KeyTiming> {
+    let original = keys.timing;
+    let timing = measure(conn, capture, window, keys);
+    keys.timing = original;
+    timing
+}
+
+fn measure<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+    keys: &mut Keys,
+) -> Result<KeyTiming> {
+    // not even the slowest delay moving the phage means it was never seen
+    let unseen = || Error::Calibration("the phage did not move, calibrate during a run");
+    let default = KeyTiming::default();
+
+    info!("Calibrating key press time");
+    let press = fastest_reliable(conn, capture, window, keys, 1, |press| KeyTiming {
+        press,
+        release: default.release,
+    })?
+    .ok_or_else(unseen)?;
+
+    info!("Calibrating key release time");
+    let release = fastest_reliable(conn, capture, window, keys, 2, |release| KeyTiming {
+        press,
+        release,
+    })?
+    .ok_or_else(unseen)?;
+
+    Ok(KeyTiming {
+        press: press + SAFETY_MARGIN,
+        release: release + SAFETY_MARGIN,
+    })
+}
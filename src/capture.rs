@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::ptr;
+use std::slice;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::pixel::{Frame, PixelFormat};
+use log::{info, warn};
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::shm;
+use x11rb::protocol::xproto::*;
+
+// a System V shared memory segment attached to both us and the X server, so
+// images arrive without being copied through the socket
+pub struct ShmSegment<'c> {
+    seg: shm::Seg,
+    addr: *mut u8,
+    size: usize,
+    // detaches the server's side, holding on to the connection for it
+    detach: Box<dyn Fn(shm::Seg) + 'c>,
+}
+
+impl<'c> ShmSegment<'c> {
+    fn new<Conn: Connection>(conn: &'c Conn, size: usize) -> Option<ShmSegment<'c>> {
+        shm::query_version(conn).ok()?.reply().ok()?;
+        let seg = conn.generate_id().ok()?;
+
+        let shmid = unsafe { libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600) };
+        if shmid < 0 {
+            return None;
+        }
+
+        let addr = unsafe { libc::shmat(shmid, ptr::null(), 0) };
+        if addr as isize == -1 {
+            unsafe { libc::shmctl(shmid, libc::IPC_RMID, ptr::null_mut()) };
+            return None;
+        }
+
+        // fails when the server is on another machine
+        let attached = shm::attach(conn, seg, shmid as u32, false)
+            .ok()
+            .and_then(|cookie| cookie.check().ok());
+
+        // the segment is freed once both sides have detached
+        unsafe { libc::shmctl(shmid, libc::IPC_RMID, ptr::null_mut()) };
+
+        if attached.is_none() {
+            unsafe { libc::shmdt(addr) };
+            return None;
+        }
+
+        Some(ShmSegment {
+            seg,
+            addr: addr as *mut u8,
+            size,
+            detach: Box::new(move |seg| {
+                // nothing is left to do about a server that is already gone
+                let _ = shm::detach(conn, seg).map(|cookie| cookie.ignore_error());
+                let _ = conn.flush();
+            }),
+        })
+    }
+
+    fn get_image<Conn: ?Sized + RequestConnection>(
+        &self,
+        conn: &Conn,
+        window: Window,
+        (x, y, width, height): (i16, i16, u16, u16),
+    ) -> Option<Vec<u8>> {
+        let reply = shm::get_image(
+            conn,
+            window,
+            x,
+            y,
+            width,
+            height,
+            !0,
+            ImageFormat::Z_PIXMAP.into(),
+            self.seg,
+            0,
+        )
+        .ok()?
+        .reply()
+        .ok()?;
+
+        let data = unsafe { slice::from_raw_parts(self.addr, reply.size as usize) };
+        Some(data.to_vec())
+    }
+}
+
+impl Drop for ShmSegment<'_> {
+    fn drop(&mut self) {
+        (self.detach)(self.seg);
+        unsafe { libc::shmdt(self.addr as *const libc::c_void) };
+    }
+}
+
+enum Backend<'c> {
+    Shm(ShmSegment<'c>),
+    GetImage,
+}
+
+pub struct Capture<'c> {
+    backend: Backend<'c>,
+    format: PixelFormat,
+    // time spent capturing and converting each image since take_timings
+    timings: RefCell<Vec<Duration>>,
+}
+
+fn plain_get_image<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    window: Window,
+    (x, y, width, height): (i16, i16, u16, u16),
+) -> Result<Vec<u8>> {
+    Ok(conn
+        .get_image(ImageFormat::Z_PIXMAP, window, x, y, width, height, !0)?
+        .reply()?
+        .data)
+}
+
+impl<'c> Capture<'c> {
+    // uses shared memory for images up to size bytes when the server allows it
+    pub fn new<Conn: Connection>(conn: &'c Conn, format: PixelFormat, size: usize) -> Capture<'c> {
+        let backend = match ShmSegment::new(conn, size) {
+            Some(segment) => Backend::Shm(segment),
+            None => {
+                warn!("MIT-SHM unavailable, capturing with GetImage");
+                Backend::GetImage
+            }
+        };
+        Capture {
+            backend,
+            format,
+            timings: RefCell::new(vec![]),
+        }
+    }
+
+    // for when the window comes back with a different visual
+    pub fn set_format(&mut self, format: PixelFormat) {
+        self.format = format;
+    }
+
+    // region is (x, y, width, height) in the window
+    pub fn get_image<Conn: ?Sized + RequestConnection>(
+        &self,
+        conn: &Conn,
+        window: Window,
+        region: (i16, i16, u16, u16),
+    ) -> Result<Frame> {
+        let start = Instant::now();
+        let (_, _, width, height) = region;
+        let mut data = None;
+        if let Backend::Shm(segment) = &self.backend {
+            if self.format.image_size(width, height) <= segment.size {
+                data = segment.get_image(conn, window, region);
+            }
+        }
+
+        let data = match data {
+            Some(data) => data,
+            None => plain_get_image(conn, window, region)?,
+        };
+        let frame = self.format.convert(data, width, height);
+
+        self.timings.borrow_mut().push(start.elapsed());
+        Ok(frame)
+    }
+
+    // needs calling regularly to keep the timings from piling up
+    pub fn take_timings(&self) -> Vec<Duration> {
+        self.timings.replace(vec![])
+    }
+}
+
+// times captures of the region with each backend
+pub fn bench<Conn: Connection>(
+    conn: &Conn,
+    window: Window,
+    format: PixelFormat,
+    region: (i16, i16, u16, u16),
+    iterations: u32,
+) -> Result<()> {
+    let (_, _, width, height) = region;
+    let mut backends = vec![(
+        "GetImage",
+        Capture {
+            backend: Backend::GetImage,
+            format,
+            timings: RefCell::new(vec![]),
+        },
+    )];
+    let shm = Capture::new(conn, format, format.image_size(width, height));
+    if let Backend::Shm(_) = shm.backend {
+        backends.push(("MIT-SHM", shm));
+    }
+
+    for (name, capture) in backends.iter() {
+        let start = Instant::now();
+        for _ in 0..iterations {
+            capture.get_image(conn, window, region)?;
+        }
+        let elapsed = start.elapsed();
+
+        info!(
+            "{}: {} captures of {}x{} in {} ms, {:.2} ms each",
+            name,
+            iterations,
+            width,
+            height,
+            elapsed.as_millis(),
+            elapsed.as_secs_f64() * 1000.0 / iterations as f64
+        );
+    }
+
+    Ok(())
+}
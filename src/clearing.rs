@@ -0,0 +1,69 @@
+use log::debug;
+use std::time::{Duration, Instant};
+
+// matched items stay outlined on screen about this long before they clear, a
+// starting point until clears have been seen
+pub const CLEAR_ANIMATION_TIME: Duration = Duration::from_millis(450);
+
+// weight given to the newest measurement
+const CLEAR_SMOOTHING: f64 = 0.3;
+// a clear seen for longer than this ran into chained matches or a stall, and
+// says little about the animation
+const MAX_CLEAR_TIME: Duration = Duration::from_secs(2);
+
+// follows matched items across readings to learn how long the game shows them
+// before they clear, and how much of that is left on the current board
+pub struct Clearing {
+    since: Option<Instant>,
+    duration: Duration,
+}
+
+impl Default for Clearing {
+    fn default() -> Clearing {
+        Clearing {
+            since: None,
+            duration: CLEAR_ANIMATION_TIME,
+        }
+    }
+}
+
+impl Clearing {
+    pub fn update(&mut self, time: Instant, clearing: bool) {
+        if clearing {
+            self.since.get_or_insert(time);
+            return;
+        }
+
+        let since = match self.since.take() {
+            Some(since) => since,
+            None => return,
+        };
+        let seen = time.saturating_duration_since(since);
+        debug!("Clearing animation seen for {} ms", seen.as_millis());
+        if seen > MAX_CLEAR_TIME {
+            return;
+        }
+
+        let old = self.duration.as_secs_f64();
+        self.duration = Duration::from_secs_f64(old + CLEAR_SMOOTHING * (seen.as_secs_f64() - old));
+    }
+
+    // how long the animation takes, as estimated so far
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    // how long the items outlined on the current board stay on screen, zero
+    // when nothing is clearing
+    pub fn remaining(&self) -> Duration {
+        match self.since {
+            Some(since) => self.duration.saturating_sub(since.elapsed()),
+            None => Duration::from_millis(0),
+        }
+    }
+
+    // a clear cut short by leaving the run is not measured
+    pub fn interrupt(&mut self) {
+        self.since = None;
+    }
+}
@@ -0,0 +1,219 @@
+use crate::logger::{Filter, Format};
+use crate::profile;
+use crate::screen::{Counter, Scene};
+use crate::window::{self, Target};
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: hack_match_bot [OPTIONS]
+
+Options:
+    --restart              start a new run from the title, game over and high
+                           score screens, once they have been learned
+    --learn <WHAT>         save the board area of the title, pause, gameover or
+                           highscore screen, which has to be showing, as the
+                           reference it is recognized by; or with score=<N> or
+                           level=<N>, the HUD counter showing N as the
+                           reference its digits are read by; then exit
+    --min-search-ms <MS>   shortest time spent searching a board [default: 20]
+    --max-search-ms <MS>   longest time spent searching a board [default: 300]
+    --window-id <ID>       play in the window with this id, as printed by
+                           xwininfo
+    --window-pid <PID>     play in the window of this process
+    --window-title <REGEX> play in the first window whose title matches
+    --profile <PATH>       where key timings and HUD areas are kept
+                           [default: $XDG_CONFIG_HOME/hack_match_bot/profile]
+    --calibrate            measure the fastest reliable key timings during a
+                           run, save them to the profile and exit
+    --bench-capture <N>    time N board captures with each capture backend
+                           and exit
+    --input <BACKEND>      send keys with xtest, uinput through a virtual
+                           keyboard in /dev/uinput, or sendevent straight to
+                           the game window without taking focus
+                           [default: xtest]
+    --verify-every <N>     check the board after every N moves and replan if a
+                           move did not land, 0 to play paths blind [default: 0]
+    --overlay              open a window showing the board as it was read,
+                           how sure the read was and the path being played
+    --dashboard <ADDR>     serve a page showing the board, path, search and
+                           score graphs at this address, such as
+                           127.0.0.1:8080
+    --metrics <ADDR>       serve Prometheus metrics on /metrics at this
+                           address, such as 127.0.0.1:9184
+    --fake-game            open a window that plays like HACK*MATCH, for
+                           testing without the game
+    --stats-json <PATH>    also write the statistics printed on exit to this
+                           file as JSON
+    --log <FILTER>         levels to log, overall and for single modules, such
+                           as info,screen=debug,main=trace; also read from
+                           $HACK_MATCH_BOT_LOG [default: info]
+    --log-format <FORMAT>  log as text, or json with one object per line
+                           [default: text]
+    -h, --help             print this message";
+
+const LOG_VARIABLE: &str = "HACK_MATCH_BOT_LOG";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputMode {
+    Xtest,
+    Uinput,
+    SendEvent,
+}
+
+impl FromStr for InputMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<InputMode, ()> {
+        match s {
+            "xtest" => Ok(InputMode::Xtest),
+            "uinput" => Ok(InputMode::Uinput),
+            "sendevent" => Ok(InputMode::SendEvent),
+            _ => Err(()),
+        }
+    }
+}
+
+// a reference to capture from the game
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Learn {
+    Scene(Scene),
+    Counter(Counter, u64),
+}
+
+impl FromStr for Learn {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Learn, ()> {
+        match s.split_once('=') {
+            Some((counter, value)) => Ok(Learn::Counter(
+                counter.parse()?,
+                value.parse().map_err(|_| ())?,
+            )),
+            None => Ok(Learn::Scene(s.parse()?)),
+        }
+    }
+}
+
+pub struct Config {
+    pub restart: bool,
+    pub min_search_time: Duration,
+    pub max_search_time: Duration,
+    pub verify_every: usize,
+    pub input: InputMode,
+    pub window: Target,
+    pub profile: PathBuf,
+    pub calibrate: bool,
+    pub learn: Option<Learn>,
+    pub bench_capture: Option<u32>,
+    pub overlay: bool,
+    pub dashboard: Option<SocketAddr>,
+    pub metrics: Option<SocketAddr>,
+    pub fake_game: bool,
+    pub stats_json: Option<PathBuf>,
+    pub log: Filter,
+    pub log_format: Format,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            restart: false,
+            min_search_time: Duration::from_millis(20),
+            max_search_time: Duration::from_millis(300),
+            verify_every: 0,
+            input: InputMode::Xtest,
+            window: Target::Game,
+            profile: profile::default_path(),
+            calibrate: false,
+            learn: None,
+            bench_capture: None,
+            overlay: false,
+            dashboard: None,
+            metrics: None,
+            fake_game: false,
+            stats_json: None,
+            log: Filter::default(),
+            log_format: Format::Text,
+        }
+    }
+}
+
+fn usage_error(message: String) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn value<T: FromStr>(arg: &str, args: &mut impl Iterator<Item = String>) -> T {
+    let value = match args.next() {
+        Some(value) => value,
+        None => usage_error(format!("Missing value for {}", arg)),
+    };
+
+    match value.parse() {
+        Ok(value) => value,
+        Err(_) => usage_error(format!("Invalid value for {}: {}", arg, value)),
+    }
+}
+
+impl Config {
+    pub fn from_args() -> Config {
+        let mut config = Config::default();
+
+        if let Ok(filter) = env::var(LOG_VARIABLE) {
+            match filter.parse() {
+                Ok(filter) => config.log = filter,
+                Err(_) => usage_error(format!("Invalid value for {}: {}", LOG_VARIABLE, filter)),
+            }
+        }
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--restart" => config.restart = true,
+                "--min-search-ms" => {
+                    config.min_search_time = Duration::from_millis(value(&arg, &mut args))
+                }
+                "--max-search-ms" => {
+                    config.max_search_time = Duration::from_millis(value(&arg, &mut args))
+                }
+                "--verify-every" => config.verify_every = value(&arg, &mut args),
+                "--input" => config.input = value(&arg, &mut args),
+                "--window-id" => {
+                    let id: String = value(&arg, &mut args);
+                    match window::parse_id(&id) {
+                        Some(id) => config.window = Target::Id(id),
+                        None => usage_error(format!("Invalid value for {}: {}", arg, id)),
+                    }
+                }
+                "--window-pid" => config.window = Target::Pid(value(&arg, &mut args)),
+                "--window-title" => config.window = Target::Title(value(&arg, &mut args)),
+                "--profile" => config.profile = value(&arg, &mut args),
+                "--calibrate" => config.calibrate = true,
+                "--learn" => config.learn = Some(value(&arg, &mut args)),
+                "--bench-capture" => config.bench_capture = Some(value(&arg, &mut args)),
+                "--overlay" => config.overlay = true,
+                "--dashboard" => config.dashboard = Some(value(&arg, &mut args)),
+                "--metrics" => config.metrics = Some(value(&arg, &mut args)),
+                "--fake-game" => config.fake_game = true,
+                "--stats-json" => config.stats_json = Some(value(&arg, &mut args)),
+                "--log" => config.log = value(&arg, &mut args),
+                "--log-format" => config.log_format = value(&arg, &mut args),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ => usage_error(format!("Unknown argument: {}", arg)),
+            }
+        }
+
+        if config.min_search_time > config.max_search_time {
+            usage_error("--min-search-ms is larger than --max-search-ms".to_string());
+        }
+
+        config
+    }
+}
@@ -0,0 +1,100 @@
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::damage::{self, ReportLevel};
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::{xfixes, Event};
+use x11rb::NONE;
+
+// tells the capture loop when the game has drawn something new, so it only
+// captures frames that can differ from the last one
+pub struct DamageWatch {
+    damage: damage::Damage,
+    // the connection's socket, waited on for events
+    fd: RawFd,
+}
+
+fn intersects(a: Rectangle, (x, y, width, height): (i16, i16, u16, u16)) -> bool {
+    let (ax, ay) = (i32::from(a.x), i32::from(a.y));
+    let (bx, by) = (i32::from(x), i32::from(y));
+    ax < bx + i32::from(width)
+        && bx < ax + i32::from(a.width)
+        && ay < by + i32::from(height)
+        && by < ay + i32::from(a.height)
+}
+
+impl DamageWatch {
+    pub fn new<Conn: ?Sized + Connection>(
+        conn: &Conn,
+        fd: RawFd,
+        window: Window,
+    ) -> Option<DamageWatch> {
+        // DAMAGE needs XFIXES negotiated first
+        xfixes::query_version(conn, 5, 0).ok()?.reply().ok()?;
+        damage::query_version(conn, 1, 1).ok()?.reply().ok()?;
+
+        let id = conn.generate_id().ok()?;
+        damage::create(conn, id, window, ReportLevel::RAW_RECTANGLES)
+            .ok()?
+            .check()
+            .ok()?;
+
+        Some(DamageWatch { damage: id, fd })
+    }
+
+    // drops any damage reported so far without waiting, so events don't pile
+    // up in the queue while nothing is waiting on them
+    pub fn clear<Conn: ?Sized + Connection>(&self, conn: &Conn) {
+        while let Ok(Some(_)) = conn.poll_for_event() {}
+        self.subtract(conn);
+    }
+
+    // the server keeps adding to the damaged area until told it has been seen
+    fn subtract<Conn: ?Sized + Connection>(&self, conn: &Conn) {
+        if let Ok(cookie) = damage::subtract(conn, self.damage, NONE, NONE) {
+            cookie.ignore_error();
+        }
+        let _ = conn.flush();
+    }
+
+    // waits until something is drawn inside region or the timeout passes,
+    // returning whether anything was drawn
+    pub fn wait<Conn: ?Sized + Connection>(
+        &self,
+        conn: &Conn,
+        region: (i16, i16, u16, u16),
+        timeout: Duration,
+    ) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let mut drawn = false;
+            while let Ok(Some(event)) = conn.poll_for_event() {
+                if let Event::DamageNotify(notify) = event {
+                    if notify.damage == self.damage && intersects(notify.area, region) {
+                        drawn = true;
+                    }
+                }
+            }
+
+            if drawn {
+                self.subtract(conn);
+                return true;
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_millis(0) {
+                return false;
+            }
+
+            let mut pollfd = libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let millis = remaining.as_millis().max(1) as libc::c_int;
+            unsafe { libc::poll(&mut pollfd, 1, millis) };
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::board::{Board, Item, MAX_COLS, MAX_ROWS};
+use crate::http;
+use crate::hud::Hud;
+use crate::search::Search;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const PAGE: &str = include_str!("dashboard.html");
+// generations kept for the graphs of a page opened mid run
+const HISTORY_LENGTH: usize = 2000;
+// sent when nothing changes for a while, so closed pages are noticed
+const KEEPALIVE_TIME: Duration = Duration::from_secs(15);
+
+#[derive(Default)]
+struct Status {
+    // the latest generation as JSON, and how many there have been
+    latest: String,
+    updates: u64,
+    // one point per generation for the graphs
+    history: VecDeque<String>,
+}
+
+type Shared = Arc<(Mutex<Status>, Condvar)>;
+
+// serves a page on a local port showing the board, path and search of each
+// generation as the bot plays them, streamed to it as server-sent events
+pub struct Dashboard {
+    shared: Shared,
+    start: Instant,
+}
+
+fn item_json(item: Item) -> String {
+    let color = match item.color() {
+        Some(color) => color.to_string(),
+        None => return "\"\"".to_string(),
+    };
+    let kind = if item.is_bomb() {
+        color.to_uppercase()
+    } else {
+        color
+    };
+    let matched = if item.is_matched() { "+" } else { "" };
+    format!("\"{}{}\"", kind, matched)
+}
+
+fn board_json(board: &Board) -> String {
+    let rows: Vec<String> = (0..MAX_ROWS)
+        .map(|row| {
+            let items: Vec<String> = (0..MAX_COLS)
+                .map(|col| item_json(board.item(row, col)))
+                .collect();
+            format!("[{}]", items.join(","))
+        })
+        .collect();
+    format!(
+        "{{\"rows\":[{}],\"phage_col\":{},\"held\":{}}}",
+        rows.join(","),
+        board.phage_col(),
+        item_json(board.held())
+    )
+}
+
+fn option_json(value: Option<u64>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+// sends every generation to the page until it goes away
+fn stream_events(stream: &mut TcpStream, shared: &Shared) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n"
+    )?;
+
+    let (lock, changed) = &**shared;
+    let mut status = lock.lock().unwrap();
+    let history: Vec<&str> = status.history.iter().map(String::as_str).collect();
+    write!(stream, "event: history\ndata: [{}]\n\n", history.join(","))?;
+
+    let mut sent = 0;
+    loop {
+        if status.updates != sent {
+            sent = status.updates;
+            let event = format!("data: {}\n\n", status.latest);
+            // the bot never waits on a slow page
+            drop(status);
+            stream.write_all(event.as_bytes())?;
+            status = lock.lock().unwrap();
+            continue;
+        }
+
+        let (next, wait) = changed.wait_timeout(status, KEEPALIVE_TIME).unwrap();
+        status = next;
+        if wait.timed_out() {
+            drop(status);
+            stream.write_all(b": keepalive\n\n")?;
+            status = lock.lock().unwrap();
+        }
+    }
+}
+
+fn serve(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    match http::read_path(&stream)?.as_str() {
+        "/" => http::respond(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE),
+        "/events" => stream_events(&mut stream, shared),
+        _ => http::not_found(&mut stream),
+    }
+}
+
+impl Dashboard {
+    pub fn start(address: SocketAddr) -> io::Result<Dashboard> {
+        let shared = Shared::default();
+        let served = shared.clone();
+        http::listen(address, move |stream| serve(stream, &served))?;
+
+        Ok(Dashboard {
+            shared,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn update(&self, generation: usize, board: &Board, hud: Hud, search: &Search) {
+        let moves: Vec<String> = search.path.iter().map(|m| format!("\"{}\"", m)).collect();
+        let point = format!(
+            "{{\"time\":{:.3},\"generation\":{},\"score\":{},\"explored\":{},\"depth\":{}}}",
+            self.start.elapsed().as_secs_f64(),
+            generation,
+            option_json(hud.score),
+            search.explored,
+            search.depth
+        );
+        let latest = format!(
+            "{{\"point\":{},\"level\":{},\"board\":{},\"path\":[{}],\"outcome\":\"{}\"}}",
+            point,
+            option_json(hud.level),
+            board_json(board),
+            moves.join(","),
+            search.outcome
+        );
+
+        let (lock, changed) = &*self.shared;
+        let mut status = lock.lock().unwrap();
+        if status.history.len() == HISTORY_LENGTH {
+            status.history.pop_front();
+        }
+        status.history.push_back(point);
+        status.latest = latest;
+        status.updates += 1;
+        changed.notify_all();
+    }
+}
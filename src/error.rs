@@ -0,0 +1,173 @@
+use std::fmt;
+use std::io;
+
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
+use x11rb::protocol::xproto::Keysym;
+use x11rb::protocol::ErrorKind;
+use x11rb::x11_utils::X11Error;
+
+// everything that stops the bot, each with what the user can do about it
+#[derive(Debug)]
+pub enum Error {
+    Connect(ConnectError),
+    ConnectionLost(ConnectionError),
+    // the server refused a request for a reason not covered below
+    X11(X11Error),
+    WindowNotFound(String),
+    WindowGone,
+    // unmapped, minimized or otherwise not drawn
+    WindowHidden,
+    WrongGeometry { width: u16, height: u16 },
+    UnsupportedVisual(String),
+    OverlayDepth(u8),
+    Dashboard(io::Error),
+    Metrics(io::Error),
+    Recognition(&'static str),
+    Calibration(&'static str),
+    NoKeycode(Keysym),
+    Uinput(io::Error),
+    Profile(io::Error),
+    Reference(io::Error),
+    Learn(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Connect(e) => write!(
+                f,
+                "Unable to connect to the X server ({}), check that DISPLAY is set",
+                e
+            ),
+            Error::ConnectionLost(e) => write!(f, "Lost the connection to the X server ({})", e),
+            Error::X11(e) => write!(
+                f,
+                "The X server refused a request ({:?} error, opcode {})",
+                e.error_kind, e.major_opcode
+            ),
+            Error::WindowNotFound(message) => write!(
+                f,
+                "{}\nStart EXAPUNKS, or pick its window with --window-id, --window-pid or --window-title",
+                message
+            ),
+            Error::WindowGone => write!(f, "The EXAPUNKS window was closed"),
+            Error::WindowHidden => write!(
+                f,
+                "The EXAPUNKS window is not visible, restore it if it is minimized"
+            ),
+            Error::WrongGeometry { width, height } => write!(
+                f,
+                "The EXAPUNKS window is {}x{}, set RESOLUTION to 1920 X 1080 in the EXAPUNKS \
+                 settings, or play fullscreen on a 1080p monitor",
+                width, height
+            ),
+            Error::UnsupportedVisual(reason) => write!(
+                f,
+                "The EXAPUNKS window uses an unsupported pixel format ({}), run the X server \
+                 with a 16, 24 or 30-bit TrueColor visual",
+                reason
+            ),
+            Error::OverlayDepth(depth) => write!(
+                f,
+                "The overlay draws on 24-bit screens and this one is {}-bit, run without \
+                 --overlay",
+                depth
+            ),
+            Error::Dashboard(e) => write!(
+                f,
+                "Unable to start the dashboard ({}), pick another address with --dashboard",
+                e
+            ),
+            Error::Metrics(e) => write!(
+                f,
+                "Unable to serve metrics ({}), pick another address with --metrics",
+                e
+            ),
+            Error::Recognition(reason) => write!(
+                f,
+                "Unable to read the game ({}), set HACK*MATCH CRT EFFECT to NO DISTORTION in \
+                 the EXAPUNKS settings",
+                reason
+            ),
+            Error::Calibration(reason) => write!(f, "Unable to calibrate ({})", reason),
+            Error::NoKeycode(sym) => write!(
+                f,
+                "No key in the keyboard layout types keysym {:#x}, switch to a layout with \
+                 A, D, J, K and Enter",
+                sym
+            ),
+            Error::Uinput(e) => write!(
+                f,
+                "Unable to create a keyboard in /dev/uinput ({}), give your user write access \
+                 to it or use another --input",
+                e
+            ),
+            Error::Profile(e) => write!(f, "Unable to save the profile ({})", e),
+            Error::Reference(e) => write!(f, "Unable to save the reference capture ({})", e),
+            Error::Learn(reason) => write!(
+                f,
+                "Unable to learn from the game ({}), check it is showing what was asked for",
+                reason
+            ),
+        }
+    }
+}
+
+impl Error {
+    // the game window went away or changed, and may come back as it was
+    pub fn is_window_problem(&self) -> bool {
+        matches!(
+            self,
+            Error::WindowNotFound(_)
+                | Error::WindowGone
+                | Error::WindowHidden
+                | Error::WrongGeometry { .. }
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ConnectError> for Error {
+    fn from(e: ConnectError) -> Error {
+        Error::Connect(e)
+    }
+}
+
+impl From<ConnectionError> for Error {
+    fn from(e: ConnectionError) -> Error {
+        Error::ConnectionLost(e)
+    }
+}
+
+impl From<X11Error> for Error {
+    fn from(e: X11Error) -> Error {
+        match e.error_kind {
+            ErrorKind::Window | ErrorKind::Drawable => Error::WindowGone,
+            // what requests on the window that need it drawn fail with
+            ErrorKind::Match => Error::WindowHidden,
+            _ => Error::X11(e),
+        }
+    }
+}
+
+impl From<ReplyError> for Error {
+    fn from(e: ReplyError) -> Error {
+        match e {
+            ReplyError::ConnectionError(e) => e.into(),
+            ReplyError::X11Error(e) => e.into(),
+        }
+    }
+}
+
+impl From<ReplyOrIdError> for Error {
+    fn from(e: ReplyOrIdError) -> Error {
+        match e {
+            ReplyOrIdError::IdsExhausted => Error::ConnectionLost(ConnectionError::UnknownError),
+            ReplyOrIdError::ConnectionError(e) => e.into(),
+            ReplyOrIdError::X11Error(e) => e.into(),
+        }
+    }
+}
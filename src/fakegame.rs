@@ -0,0 +1,150 @@
+use crate::board;
+use crate::board::{Board, Color, Item, Move};
+use crate::clearing::CLEAR_ANIMATION_TIME;
+use crate::error::Result;
+use crate::render;
+use crate::screen::{BOARD_REGION, WINDOW_HEIGHT, WINDOW_WIDTH};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+const TITLE: &[u8] = b"EXAPUNKS";
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+const XK_A: Keysym = 0x0061;
+const XK_D: Keysym = 0x0064;
+const XK_J: Keysym = 0x006a;
+const XK_K: Keysym = 0x006b;
+
+// a board the bot can clear in one move: swapping the bottom two items of the
+// middle column lines up four yellow files
+fn start_board() -> Board {
+    use Color::*;
+    use Item::*;
+
+    let mut items = [[Empty; board::MAX_COLS]; board::MAX_ROWS];
+    items[0] = [
+        File(Yellow),
+        File(Yellow),
+        File(Yellow),
+        File(Red),
+        File(Blue),
+        File(Cyan),
+        File(Pink),
+    ];
+    items[1] = [
+        File(Red),
+        File(Cyan),
+        File(Blue),
+        File(Yellow),
+        File(Pink),
+        File(Red),
+        File(Cyan),
+    ];
+    board::make_board(3, Empty, items)
+}
+
+// stands in for EXAPUNKS on a 24-bit X server: a window with the game's title
+// showing a board that follows the keys the bot sends, printing "Cleared"
+// every time a match clears
+pub fn run() -> Result<()> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let setup = conn.setup();
+    let screen = &setup.roots[screen_num];
+
+    let window = conn.generate_id()?;
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        screen.root,
+        0,
+        0,
+        WINDOW_WIDTH,
+        WINDOW_HEIGHT,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new()
+            .background_pixel(screen.black_pixel)
+            .event_mask(EventMask::KEY_PRESS | EventMask::EXPOSURE),
+    )?;
+    conn.change_property8(
+        PropMode::REPLACE,
+        window,
+        AtomEnum::WM_NAME,
+        AtomEnum::STRING,
+        TITLE,
+    )?;
+    let gc = conn.generate_id()?;
+    conn.create_gc(gc, window, &CreateGCAux::new())?;
+    conn.map_window(window)?;
+    conn.flush()?;
+
+    let mapping = conn
+        .get_keyboard_mapping(setup.min_keycode, setup.max_keycode - setup.min_keycode + 1)?
+        .reply()?;
+    let keysym = |code: Keycode| {
+        let index = (code - setup.min_keycode) as usize * mapping.keysyms_per_keycode as usize;
+        mapping.keysyms.get(index).copied()
+    };
+
+    // printed rather than logged, tests/xvfb.rs reads these lines
+    println!("Fake game window {:#x}", window);
+
+    let mut board = start_board();
+    let mut clear_at = None;
+    let mut dirty = true;
+
+    loop {
+        while let Some(event) = conn.poll_for_event()? {
+            match event {
+                Event::Expose(_) => dirty = true,
+                Event::KeyPress(press) => {
+                    let m = match keysym(press.detail) {
+                        Some(XK_A) => Move::Left,
+                        Some(XK_D) => Move::Right,
+                        Some(XK_K) => Move::Swap,
+                        Some(XK_J) => Move::Exchange,
+                        _ => continue,
+                    };
+                    board = board.predict(&[m]);
+                    dirty = true;
+                }
+                _ => (),
+            }
+        }
+
+        if board.is_clearing() {
+            let at = *clear_at.get_or_insert_with(|| Instant::now() + CLEAR_ANIMATION_TIME);
+            if Instant::now() >= at {
+                // clearing can leave new matches behind
+                board = board.cleared().predict(&[]);
+                clear_at = None;
+                dirty = true;
+                println!("Cleared");
+            }
+        }
+
+        if dirty {
+            let (x, y, _, _) = BOARD_REGION;
+            render::put_board(
+                &conn,
+                window,
+                gc,
+                screen.root_depth,
+                x,
+                y,
+                &render::render(&board),
+            )?;
+            conn.flush()?;
+            dirty = false;
+        }
+
+        thread::sleep(FRAME_TIME);
+    }
+}
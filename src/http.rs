@@ -0,0 +1,57 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+// just enough HTTP for the dashboard and metrics, every request gets a thread
+// running serve
+pub fn listen<F>(address: SocketAddr, serve: F) -> io::Result<()>
+where
+    F: Fn(TcpStream) -> io::Result<()> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(address)?;
+    let serve = Arc::new(serve);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let serve = serve.clone();
+            // a client going away mid response is nothing to report
+            thread::spawn(move || serve(stream).ok());
+        }
+    });
+    Ok(())
+}
+
+// reads a request, returning the path it asks for
+pub fn read_path(stream: &TcpStream) -> io::Result<String> {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers say nothing we need
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    Ok(request.split_whitespace().nth(1).unwrap_or("").to_string())
+}
+
+pub fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+pub fn not_found(stream: &mut TcpStream) -> io::Result<()> {
+    respond(stream, "404 Not Found", "text/plain", "Not found\n")
+}
@@ -0,0 +1,297 @@
+use crate::pixel::BYTES_PER_PIXEL;
+use crate::reference::Image;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Hud {
+    pub score: Option<u64>,
+    pub level: Option<u64>,
+}
+
+// digits are drawn bright on a dark background
+const DIGIT_THRESHOLD: u8 = 160;
+// share of a glyph's cells that may differ from a learned one and still be
+// read as it
+const MAX_GLYPH_ERROR: f64 = 0.15;
+
+fn lit(image: &Image, x: usize, y: usize) -> bool {
+    let offset = BYTES_PER_PIXEL * (image.width * y + x);
+    image.data[offset..offset + 3]
+        .iter()
+        .any(|c| *c >= DIGIT_THRESHOLD)
+}
+
+fn column_lit(image: &Image, x: usize) -> bool {
+    (0..image.height).any(|y| lit(image, x, y))
+}
+
+// the lit cells of one character, cropped to them
+#[derive(Debug, PartialEq)]
+struct Glyph {
+    width: usize,
+    height: usize,
+    cells: Vec<bool>,
+}
+
+impl Glyph {
+    fn cell(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.cells[self.width * y + x]
+    }
+
+    // cells that differ, None when the sizes are too far apart for the two
+    // to be the same character
+    fn distance(&self, other: &Glyph) -> Option<usize> {
+        if self.width.abs_diff(other.width) > 1 || self.height.abs_diff(other.height) > 1 {
+            return None;
+        }
+
+        let width = self.width.max(other.width);
+        let height = self.height.max(other.height);
+        Some(
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|(x, y)| self.cell(*x, *y) != other.cell(*x, *y))
+                .count(),
+        )
+    }
+}
+
+// splits a row of characters separated by blank columns into glyphs
+fn glyphs(image: &Image) -> Vec<Glyph> {
+    let mut found = vec![];
+    let mut x = 0;
+    while x < image.width {
+        if !column_lit(image, x) {
+            x += 1;
+            continue;
+        }
+
+        let left = x;
+        while x < image.width && column_lit(image, x) {
+            x += 1;
+        }
+        let right = x;
+
+        let rows: Vec<usize> = (0..image.height)
+            .filter(|y| (left..right).any(|x| lit(image, x, *y)))
+            .collect();
+        let (top, bottom) = (rows[0], rows[rows.len() - 1] + 1);
+        found.push(Glyph {
+            width: right - left,
+            height: bottom - top,
+            cells: (top..bottom)
+                .flat_map(|y| (left..right).map(move |x| lit(image, x, y)))
+                .collect(),
+        });
+    }
+    found
+}
+
+// glyphs tall enough to be digits, leaving out thousands separators and other
+// punctuation drawn between them
+fn characters(image: &Image) -> Vec<Glyph> {
+    let found = glyphs(image);
+    let tallest = found.iter().map(|glyph| glyph.height).max().unwrap_or(0);
+    found
+        .into_iter()
+        .filter(|glyph| glyph.height * 2 > tallest)
+        .collect()
+}
+
+fn decimal_digits(number: u64) -> Vec<u64> {
+    number
+        .to_string()
+        .bytes()
+        .map(|b| u64::from(b - b'0'))
+        .collect()
+}
+
+// digit shapes learned from captures of the HUD showing known numbers, so
+// they are drawn in the game's own font at its own size
+#[derive(Default)]
+pub struct Digits {
+    known: Vec<(u64, Glyph)>,
+}
+
+impl Digits {
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+
+    // digits with no learned shape, which make any number with them unreadable
+    pub fn missing(&self) -> Vec<u64> {
+        (0..10)
+            .filter(|digit| !self.known.iter().any(|(known, _)| known == digit))
+            .collect()
+    }
+
+    // learns the shapes of number's digits from an image showing it, returns
+    // false without learning any when the image holds a different number of
+    // digits
+    pub fn learn(&mut self, image: &Image, number: u64) -> bool {
+        let found = characters(image);
+        let digits = decimal_digits(number);
+        if found.len() != digits.len() {
+            return false;
+        }
+
+        for (digit, glyph) in digits.into_iter().zip(found) {
+            if !self
+                .known
+                .iter()
+                .any(|(known, shape)| *known == digit && *shape == glyph)
+            {
+                self.known.push((digit, glyph));
+            }
+        }
+        true
+    }
+
+    fn read_digit(&self, glyph: &Glyph) -> Option<u64> {
+        let (digit, error, cells) = self
+            .known
+            .iter()
+            .filter_map(|(digit, shape)| Some((*digit, shape.distance(glyph)?, shape.cells.len())))
+            .min_by_key(|(_, error, _)| *error)?;
+
+        if error as f64 <= cells as f64 * MAX_GLYPH_ERROR {
+            Some(digit)
+        } else {
+            None
+        }
+    }
+
+    // reads a row of digits separated by blank columns, None if anything in
+    // the image other than separators is not a learned digit
+    pub fn read_number(&self, image: &Image) -> Option<u64> {
+        let mut number: Option<u64> = None;
+        for glyph in characters(image) {
+            let digit = self.read_digit(&glyph)?;
+            number = Some(number.unwrap_or(0).checked_mul(10)?.checked_add(digit)?);
+        }
+        number
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALE: usize = 3;
+    const SPACING: usize = 4;
+    const WIDTH: usize = 300;
+    const HEIGHT: usize = 40;
+
+    // a 5x7 font standing in for the game's, one row per byte with the
+    // leftmost column in bit 4
+    #[rustfmt::skip]
+    const FONT: [[u8; 7]; 10] = [
+        [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+    ];
+
+    #[rustfmt::skip]
+    const COMMA: [u8; 7] = [0, 0, 0, 0, 0b00110, 0b00110, 0b00100];
+
+    // a HUD counter drawing text light on a dark, slightly noisy background
+    fn counter_text(text: &str) -> Image {
+        let mut data = vec![0; BYTES_PER_PIXEL * WIDTH * HEIGHT];
+        for (i, channel) in data.iter_mut().enumerate() {
+            *channel = (i * 7 % 40) as u8;
+        }
+
+        let mut left = 10;
+        for c in text.chars() {
+            let shape = match c.to_digit(10) {
+                Some(digit) => &FONT[digit as usize],
+                None => &COMMA,
+            };
+            for (row, bits) in shape.iter().enumerate() {
+                for col in 0..5 {
+                    if bits & 1 << (4 - col) == 0 {
+                        continue;
+                    }
+                    for y in 0..SCALE {
+                        for x in 0..SCALE {
+                            let x = left + col * SCALE + x;
+                            let y = 8 + row * SCALE + y;
+                            let offset = BYTES_PER_PIXEL * (WIDTH * y + x);
+                            data[offset..offset + 3].copy_from_slice(&[230, 240, 250]);
+                        }
+                    }
+                }
+            }
+            left += 5 * SCALE + SPACING;
+        }
+
+        Image {
+            data,
+            width: WIDTH,
+            height: HEIGHT,
+        }
+    }
+
+    fn counter(number: u64) -> Image {
+        counter_text(&number.to_string())
+    }
+
+    #[test]
+    fn reads_numbers_in_learned_digits() {
+        let mut digits = Digits::default();
+        assert!(digits.learn(&counter(1234567890), 1234567890));
+        assert!(digits.missing().is_empty());
+
+        for number in [0, 7, 42, 8731200, 9081726354].iter() {
+            assert_eq!(digits.read_number(&counter(*number)), Some(*number));
+        }
+    }
+
+    #[test]
+    fn unlearned_digits_are_unreadable() {
+        let mut digits = Digits::default();
+        assert!(digits.learn(&counter(1230), 1230));
+        assert_eq!(digits.missing(), vec![4, 5, 6, 7, 8, 9]);
+
+        assert_eq!(digits.read_number(&counter(3021)), Some(3021));
+        assert_eq!(digits.read_number(&counter(1204)), None);
+    }
+
+    #[test]
+    fn learning_needs_every_digit_shown() {
+        let mut digits = Digits::default();
+        assert!(!digits.learn(&counter(123), 1234));
+        assert!(digits.is_empty());
+    }
+
+    #[test]
+    fn separators_are_skipped() {
+        let mut digits = Digits::default();
+        assert!(digits.learn(&counter_text("1,234,567,890"), 1234567890));
+        assert!(digits.missing().is_empty());
+
+        assert_eq!(
+            digits.read_number(&counter_text("8,731,200")),
+            Some(8731200)
+        );
+        assert_eq!(digits.read_number(&counter(8731200)), Some(8731200));
+    }
+
+    #[test]
+    fn a_blank_counter_reads_as_nothing() {
+        let mut digits = Digits::default();
+        digits.learn(&counter(1234567890), 1234567890);
+        let blank = Image {
+            data: vec![0; BYTES_PER_PIXEL * WIDTH * HEIGHT],
+            width: WIDTH,
+            height: HEIGHT,
+        };
+        assert_eq!(digits.read_number(&blank), None);
+    }
+}
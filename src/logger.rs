@@ -0,0 +1,156 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Instant;
+
+const CRATE_TARGET: &str = "hack_match_bot";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Format, ()> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+// which levels to log, as a default level followed by overrides for single
+// modules, such as "info,screen=debug,main=trace"
+#[derive(Clone, Debug)]
+pub struct Filter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter {
+            default: LevelFilter::Info,
+            modules: vec![],
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Filter, ()> {
+        let mut filter = Filter::default();
+        for part in s.split(',').filter(|part| !part.is_empty()) {
+            match part.split_once('=') {
+                Some((module, level)) => filter
+                    .modules
+                    .push((module.to_string(), level.parse().map_err(|_| ())?)),
+                None => filter.default = part.parse().map_err(|_| ())?,
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl Filter {
+    fn level(&self, module: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .rev()
+            .find(|(name, _)| name == module)
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+// the module a target names, main for the crate root
+fn module(target: &str) -> &str {
+    if target == CRATE_TARGET {
+        "main"
+    } else {
+        target
+            .strip_prefix(CRATE_TARGET)
+            .and_then(|rest| rest.strip_prefix("::"))
+            .unwrap_or(target)
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// writes records to stderr, one line each as text or json
+struct Logger {
+    filter: Filter,
+    format: Format,
+    start: Instant,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(module(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time = self.start.elapsed().as_secs_f64();
+        let module = module(record.target());
+        let line = match self.format {
+            Format::Text => format!(
+                "{:>10.3} {:<5} {}: {}\n",
+                time,
+                record.level(),
+                module,
+                record.args()
+            ),
+            Format::Json => format!(
+                "{{\"time\":{:.3},\"level\":\"{}\",\"module\":\"{}\",\"message\":\"{}\"}}\n",
+                time,
+                record.level(),
+                module,
+                escape(&record.args().to_string())
+            ),
+        };
+        // a closed stderr leaves nowhere to report it
+        let _ = io::stderr().lock().write_all(line.as_bytes());
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+pub fn init(filter: Filter, format: Format) {
+    log::set_max_level(filter.max_level());
+    let logger = Logger {
+        filter,
+        format,
+        start: Instant::now(),
+    };
+    // only fails if a logger is already set, which would be a bug
+    log::set_boxed_logger(Box::new(logger)).expect("Logger initialized twice");
+}
@@ -1,129 +1,352 @@
+#![allow(clippy::needless_range_loop)]
+
 mod board;
+mod budget;
+mod calibrate;
+mod capture;
+mod clearing;
+mod config;
+mod damage;
+mod dashboard;
+mod error;
+mod fakegame;
+mod http;
+mod hud;
+mod logger;
+mod metrics;
+mod overlay;
+mod pixel;
+mod profile;
+mod reference;
+mod render;
 mod screen;
+mod scroll;
+mod search;
+mod state;
+mod stats;
+mod uinput;
+mod window;
 
 use board::{Board, Item, Move};
+use budget::Budget;
+use capture::Capture;
+use clearing::Clearing;
+use config::{Config, InputMode, Learn};
+use damage::DamageWatch;
+use dashboard::Dashboard;
+use error::{Error, Result};
+use hud::Hud;
+use log::{debug, error, info, warn};
+use metrics::Metrics;
+use overlay::Overlay;
+use profile::Profile;
+use screen::{Confidence, HudAreas, Input, Keys, Learned, Observation};
+use scroll::Scroll;
+use search::{find_match, Search, Speculation};
+use state::GameState;
+use stats::Stats;
 
-use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::os::unix::io::AsRawFd;
+use std::process;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
 use x11rb::connection::Connection;
+use x11rb::protocol::xproto::Window;
+use x11rb::rust_connection::RustConnection;
 
-const MAX_SEARCH_TIME: Duration = Duration::from_millis(110);
-const SOLVE_WAIT_TIME: Duration = Duration::from_millis(4 * screen::KEY_DELAY_MILLIS + 12);
-
-fn find_match(start: &Board) -> Vec<Move> {
-    if start.has_match() {
-        println!("Board already has an unrealized match");
-        return vec![];
-    }
-
-    let start_time = Instant::now();
+// time after playing a path, on top of two moves, for the game to show the result
+const SOLVE_WAIT_MARGIN: Duration = Duration::from_millis(12);
 
-    let mut boards = VecDeque::with_capacity(10000);
-    let mut seen = HashSet::with_capacity(80000);
+// how often a learned scene is looked at while waiting for a run
+const SCREEN_POLL_TIME: Duration = Duration::from_millis(100);
+// the game takes a moment to leave the title and game over scenes, pressing
+// Enter again before then would skip past the next one
+const RESTART_RETRY_TIME: Duration = Duration::from_secs(2);
 
-    let mut highest_score = start.score();
-    let mut highest_path = vec![];
+fn moves_within(time: Duration, move_time: Duration) -> usize {
+    (time.as_millis() / move_time.as_millis().max(1)) as usize
+}
 
-    let mut explored = 1;
-    let mut steps_ahead = 0;
+// optional ways of watching the bot play
+struct Watchers {
+    overlay: Option<Overlay>,
+    dashboard: Option<Dashboard>,
+    stats: Arc<Mutex<Stats>>,
+    metrics: Option<Metrics>,
+}
 
-    seen.insert(start.clone());
-    boards.push_back((start.clone(), vec![]));
+impl Watchers {
+    // the overlay is only for watching, losing it does not stop play
+    fn raise_overlay<Conn: Connection>(&mut self, conn: &Conn) {
+        if let Some(overlay) = &self.overlay {
+            if let Err(e) = overlay.raise(conn) {
+                warn!("Closing the overlay: {}", e);
+                self.overlay = None;
+            }
+        }
+    }
 
-    while !boards.is_empty() {
-        if Instant::now().duration_since(start_time) > MAX_SEARCH_TIME {
-            if highest_path.len() == 0 {
-                println!("Search timed out, could not find a match or better board");
-            } else {
-                println!("Search timed out, defaulting to highest score");
+    fn show_overlay<Conn: Connection>(
+        &mut self,
+        conn: &Conn,
+        board: &Board,
+        confidence: &Confidence,
+        y_offset: usize,
+        path: &[Move],
+    ) {
+        if let Some(overlay) = &self.overlay {
+            if let Err(e) = overlay.show(conn, board, confidence, y_offset, path) {
+                warn!("Closing the overlay: {}", e);
+                self.overlay = None;
             }
-            println!(
-                "Explored {} boards, {} moves deep, returning path {} long",
-                explored,
-                steps_ahead,
-                highest_path.len()
-            );
-            return highest_path;
         }
+    }
 
-        let (board, path) = boards.pop_front().unwrap();
-        if path.len() > steps_ahead {
-            steps_ahead = path.len() + 1;
+    // returns the time the captures took
+    fn record_captures(&self, capture: &Capture) -> Duration {
+        let timings = capture.take_timings();
+        self.stats.lock().unwrap().record_captures(&timings);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_captures(&timings);
         }
+        timings.iter().sum()
+    }
 
-        for m in [Move::Left, Move::Right, Move::Swap, Move::Exchange].iter() {
-            let new_board = board.do_move(*m);
-            if !seen.contains(&new_board) {
-                explored += 1;
+    fn record_recognition_failures(&self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.stats
+            .lock()
+            .unwrap()
+            .record_recognition_failures(count);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_recognition_failures(count);
+        }
+    }
 
-                // Create the path to this board
-                let mut new_path = path.clone();
-                new_path.push(*m);
+    fn record_search(&self, search: &Search, hud: Hud, waited: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.record_search(search, waited);
+        stats.record_hud(hud);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_search(search, waited);
+            metrics.record_score(hud.score);
+        }
+    }
 
-                // check if the board has a match on it
-                if new_board.has_match() {
-                    println!("Found match");
-                    println!(
-                        "Explored {} boards, {} moves deep, returning path {} long",
-                        explored,
-                        steps_ahead + 1,
-                        new_path.len()
-                    );
-                    return new_path;
-                }
+    fn record_path(&self, moves: usize, elapsed: Duration) {
+        self.stats.lock().unwrap().record_path(moves, elapsed);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_path(moves);
+        }
+    }
+}
 
-                // Add the board to the seen list
-                seen.insert(new_board.clone());
+// prints the session's statistics and writes them to the --stats-json file
+fn report(config: &Config, stats: &Stats) {
+    println!("{}", stats);
+    if let Some(path) = &config.stats_json {
+        if let Err(e) = fs::write(path, stats.json()) {
+            error!("Unable to write statistics to {} ({})", path.display(), e);
+        }
+    }
+}
 
-                // check if the board has a higher score
-                let new_score = new_board.score();
-                if new_score > highest_score {
-                    highest_score = new_score;
-                    highest_path = new_path.clone();
-                }
+fn main() {
+    let config = Config::from_args();
+    logger::init(config.log.clone(), config.log_format);
 
-                // Push the board onto the worklist
-                boards.push_back((new_board, new_path));
-            }
+    if config.fake_game {
+        if let Err(e) = fakegame::run() {
+            error!("{}", e);
+            process::exit(1);
         }
+        return;
     }
 
-    // panic!("find_match() cannot be called on a board with no matches");
-    if highest_path.len() == 0 {
-        println!("Exhausted search, could not find a match or better board");
-    } else {
-        println!("Exhausted search, defaulting to highest score");
+    let config = Arc::new(config);
+    let stats = Arc::new(Mutex::new(Stats::new()));
+    let reported = (config.clone(), stats.clone());
+    let handled = ctrlc::set_handler(move || {
+        let (config, stats) = &reported;
+        report(config, &stats.lock().unwrap());
+        process::exit(130);
+    });
+    if let Err(e) = handled {
+        warn!(
+            "Unable to catch Ctrl-C, no report will be printed on it ({})",
+            e
+        );
+    }
+
+    let result = run(&config, stats.clone());
+    // nothing to report when the bot never got to play
+    let stats = stats.lock().unwrap();
+    if stats.generations() > 0 {
+        report(&config, &stats);
+    }
+    if let Err(e) = result {
+        error!("{}", e);
+        process::exit(1);
     }
-    println!(
-        "Explored {} boards, {} moves deep, returning path {} long",
-        explored,
-        steps_ahead,
-        highest_path.len()
-    );
-    highest_path
 }
 
-fn main() {
-    let (conn, screen_num) = x11rb::connect(None).unwrap();
+fn run(config: &Config, stats: Arc<Mutex<Stats>>) -> Result<()> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
     let setup = conn.setup();
     let screen = &setup.roots[screen_num];
 
-    let keycodes = screen::get_keycodes(&conn, &setup);
+    info!("Finding EXAPUNKS window");
+    let mut window = window::find(&conn, screen.root, &config.window)?;
 
-    println!("{:?}", keycodes);
+    info!("Validating window parameters");
+    let format = screen::validate_window(&conn, setup, screen, window)?;
 
-    println!("Finding EXAPUNKS window");
-    let window = match screen::get_exapunks_window(&conn, screen.root) {
-        Some(window) => window,
-        None => panic!("Unable to find EXAPUNKS window."),
+    let mut profile = Profile::load(&config.profile);
+    let input = match config.input {
+        InputMode::Xtest => Input::Xtest,
+        InputMode::Uinput => Input::Uinput(uinput::Keyboard::new().map_err(Error::Uinput)?),
+        InputMode::SendEvent => Input::SendEvent {
+            root: screen.root,
+            window,
+        },
     };
+    let mut keys = screen::get_keys(&conn, setup, input, profile.key_timing)?;
+
+    debug!("{:?}", keys);
+
+    let dashboard = match config.dashboard {
+        Some(address) => {
+            let dashboard = Dashboard::start(address).map_err(Error::Dashboard)?;
+            info!("Dashboard at http://{}/", address);
+            Some(dashboard)
+        }
+        None => None,
+    };
+
+    let overlay = if config.overlay {
+        Some(Overlay::new(&conn, screen, window)?)
+    } else {
+        None
+    };
+    let metrics = match config.metrics {
+        Some(address) => {
+            let metrics = Metrics::start(address).map_err(Error::Metrics)?;
+            info!("Metrics at http://{}/metrics", address);
+            Some(metrics)
+        }
+        None => None,
+    };
+    let mut watchers = Watchers {
+        overlay,
+        dashboard,
+        stats,
+        metrics,
+    };
+
+    // keys sent with XSendEvent reach the window wherever focus is
+    if config.input != InputMode::SendEvent {
+        screen::activate_window(&conn, window)?;
+    }
+    watchers.raise_overlay(&conn);
+
+    if let Some(iterations) = config.bench_capture {
+        return capture::bench(&conn, window, format, screen::BOARD_REGION, iterations);
+    }
 
-    println!("Validating window parameters");
-    screen::validate_window(&conn, &setup, &screen, window);
+    let mut capture = Capture::new(&conn, format, screen::BOARD_IMAGE_BYTES);
+    let mut damage = DamageWatch::new(&conn, conn.stream().as_raw_fd(), window);
+    if damage.is_none() {
+        warn!("DAMAGE unavailable, polling for new frames");
+    }
+
+    if config.calibrate {
+        let timing = calibrate::calibrate(&conn, &capture, window, &mut keys)?;
+        info!("Calibrated key timing: {:?}", timing);
+        profile.key_timing = timing;
+        profile.save(&config.profile).map_err(Error::Profile)?;
+        info!("Saved profile to {}", config.profile.display());
+        return Ok(());
+    }
+
+    let references = reference::dir(&config.profile);
+    if let Some(learn) = config.learn {
+        let path = match learn {
+            Learn::Scene(scene) => {
+                screen::learn_scene(&conn, &capture, window, scene, &references)?
+            }
+            Learn::Counter(counter, value) => screen::learn_counter(
+                &conn,
+                &capture,
+                window,
+                &profile.hud_areas,
+                counter,
+                value,
+                &references,
+            )?,
+        };
+        info!(
+            "Saved {}, open it to check it shows what was asked for",
+            path.display()
+        );
+        return Ok(());
+    }
+    let learned = Learned::load(&references);
+
+    // the game window closing, hiding or resizing pauses play until it is
+    // usable again
+    loop {
+        let e = match play(
+            config,
+            &conn,
+            &capture,
+            damage.as_ref(),
+            window,
+            &learned,
+            &profile.hud_areas,
+            &keys,
+            &mut watchers,
+        ) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.is_window_problem() => e,
+            Err(e) => return Err(e),
+        };
+        warn!("{}, waiting for it to come back", e);
+
+        let (found, format) = window::wait_until_usable(&conn, setup, screen, &config.window, &e)?;
+        capture.set_format(format);
+        if found != window {
+            window = found;
+            keys.set_window(window);
+            damage = DamageWatch::new(&conn, conn.stream().as_raw_fd(), window);
+        }
+        if config.input != InputMode::SendEvent {
+            screen::activate_window(&conn, window)?;
+        }
+        watchers.raise_overlay(&conn);
+        info!("Window is back, resuming");
+    }
+}
 
-    screen::activate_window(&conn, window);
+// plays until something goes wrong
+#[allow(clippy::too_many_arguments)]
+fn play<Conn: Connection>(
+    config: &Config,
+    conn: &Conn,
+    capture: &Capture,
+    damage: Option<&DamageWatch>,
+    window: Window,
+    learned: &Learned,
+    hud_areas: &HudAreas,
+    keys: &Keys,
+    watchers: &mut Watchers,
+) -> Result<()> {
+    let solve_wait_time = keys.timing.move_time() * 2 + SOLVE_WAIT_MARGIN;
 
     let mut board = board::make_board(
         0,
@@ -131,18 +354,173 @@ fn main() {
         [[Item::Empty; board::MAX_COLS]; board::MAX_ROWS],
     );
     let mut generation = 0;
+    let mut speculation: Option<Speculation> = None;
+    let no_cancel = AtomicBool::new(false);
+    let mut clearing = Clearing::default();
+    let mut state = GameState::Menu;
+    let mut started: Option<Instant> = None;
+    let mut scroll = Scroll::default();
+    let budget = Budget::new(
+        config.min_search_time,
+        config.max_search_time,
+        keys.timing.move_time(),
+    );
+
+    let mut diverged = None;
 
     loop {
-        board = screen::get_new_board(&conn, window, &board);
-
-        println!("Generation: {}", generation);
-        board.print();
-        println!("Solving board");
-        let path = find_match(&board);
-        println!("Playing path {:?}", path);
-        screen::play_path(&conn, &keycodes, path);
+        let (observation, failed_reads) = match diverged.take() {
+            Some(reading) => (Observation::NewBoard(reading), 0),
+            None => screen::observe(conn, capture, damage, window, &learned.scenes, &board)?,
+        };
+        watchers.record_captures(capture);
+        // menus and game over screens hold no board to begin with
+        if state == GameState::Playing {
+            watchers.record_recognition_failures(failed_reads);
+        }
+
+        let next_state = state.next(&observation);
+        if next_state != state {
+            info!("Game state: {:?} -> {:?}", state, next_state);
+            if let Observation::NoBoard(e) = &observation {
+                warn!("{}", e);
+            }
+            if let Some(spec) = speculation.take() {
+                spec.discard();
+            }
+            if next_state.is_over() {
+                scroll.reset();
+            }
+            clearing.interrupt();
+            state = next_state;
+        }
+
+        let (confidence, y_offset) = match observation {
+            Observation::NewBoard(reading) => {
+                scroll.update(reading.time, reading.y_offset);
+                clearing.update(reading.time, reading.board.is_clearing());
+                board = reading.board;
+                (reading.confidence, reading.y_offset)
+            }
+            Observation::Frozen | Observation::NoBoard(_) => {
+                // a resized window has a board that cannot be read
+                screen::check_geometry(conn, window)?;
+                continue;
+            }
+            Observation::Title | Observation::Pause | Observation::GameOver => {
+                let retry = started.is_none_or(|at| at.elapsed() > RESTART_RETRY_TIME);
+                if config.restart && state.is_over() && retry {
+                    info!("Starting a new run");
+                    screen::press_start(conn, keys)?;
+                    started = Some(Instant::now());
+                }
+                thread::sleep(SCREEN_POLL_TIME);
+                continue;
+            }
+        };
+
+        info!("Generation: {}", generation);
+        let hud = screen::read_hud(conn, capture, window, hud_areas, &learned.digits)?;
+        match (hud.score, hud.level) {
+            (Some(score), Some(level)) => info!("Score: {}, level: {}", score, level),
+            (Some(score), None) => info!("Score: {}", score),
+            _ if learned.digits.is_empty() => (),
+            _ => warn!("Could not read score"),
+        }
+        info!("Board:\n{}", board);
+
+        let next_row = scroll.time_to_next_row();
+        if let (Some(speed), Some(next_row)) = (scroll.speed(), next_row) {
+            debug!(
+                "Scrolling {:.1} px/s, next row in {} ms",
+                speed,
+                next_row.as_millis()
+            );
+        }
+        let incoming = budget.rows_incoming(next_row);
+        let search_time = budget.for_board(board.danger(incoming), next_row);
+        debug!(
+            "Searching for {} ms, moves take {} ms",
+            search_time.as_millis(),
+            budget.move_time().as_millis()
+        );
+
+        // matched items stay outlined for a while before they clear, only the
+        // moves sent within that window act on the board as it is shown
+        let clearing_moves = moves_within(clearing.remaining(), budget.move_time());
+        let search_start = Instant::now();
+        let search = match speculation.take() {
+            Some(spec) if spec.board == board => {
+                debug!("Board matches prediction, using precomputed path");
+                spec.search.join().unwrap()
+            }
+            Some(spec) => {
+                debug!("Board differs from prediction, discarding precomputed path");
+                spec.discard();
+                debug!("Solving board");
+                find_match(&board, clearing_moves, incoming, search_time, &no_cancel)
+            }
+            None => {
+                debug!("Solving board");
+                find_match(&board, clearing_moves, incoming, search_time, &no_cancel)
+            }
+        };
+
+        let waited = search_start.elapsed();
+
+        if let Some(dashboard) = &watchers.dashboard {
+            dashboard.update(generation, &board, hud, &search);
+        }
+        watchers.record_search(&search, hud, waited);
+        let path = search.path;
+
+        // an empty path leaves the board as it is, which observe() skips over
+        if !path.is_empty() {
+            // a predicted match starts clearing as the last key of the path lands
+            let clearing_moves = moves_within(
+                clearing.duration().saturating_sub(solve_wait_time),
+                budget.move_time(),
+            );
+            // the next row is that much closer once the path has been played
+            let played = budget.move_time() * path.len() as u32 + solve_wait_time;
+            let incoming = budget.rows_incoming(next_row.map(|t| t.saturating_sub(played)));
+            speculation = Some(Speculation::start(
+                board.predict(&path),
+                clearing_moves,
+                incoming,
+                search_time,
+            ));
+        }
+
+        watchers.show_overlay(conn, &board, &confidence, y_offset, &path);
+
+        info!("Playing path {:?}", path);
+        let moves = path.len();
+        let play_start = Instant::now();
+        if config.verify_every > 0 {
+            let (reading, failed_reads) = screen::play_path_verified(
+                conn,
+                capture,
+                window,
+                keys,
+                &board,
+                path,
+                config.verify_every,
+            )?;
+            watchers.record_recognition_failures(failed_reads);
+            diverged = reading;
+        } else {
+            screen::play_path(conn, keys, path)?;
+        }
         generation += 1;
-        println!();
-        thread::sleep(SOLVE_WAIT_TIME);
+
+        // checking the moves landed counts as capture time
+        let verify_time = watchers.record_captures(capture);
+        watchers.record_path(moves, play_start.elapsed().saturating_sub(verify_time));
+
+        // a diverged path is replanned straight away from the board it left
+        if diverged.is_none() {
+            thread::sleep(solve_wait_time);
+        }
     }
 }
@@ -0,0 +1,233 @@
+use crate::http;
+use crate::search::{Outcome, Search};
+use std::fmt::Write;
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const PREFIX: &str = "hack_match_bot";
+
+const SEARCH_SECONDS_BUCKETS: [f64; 8] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+const EXPLORED_BUCKETS: [f64; 7] = [100.0, 1e3, 5e3, 1e4, 5e4, 1e5, 5e5];
+const PATH_LENGTH_BUCKETS: [f64; 9] = [0.0, 1.0, 2.0, 3.0, 4.0, 6.0, 8.0, 12.0, 16.0];
+const CAPTURE_SECONDS_BUCKETS: [f64; 7] = [0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1];
+
+const OUTCOMES: [Outcome; 5] = [
+    Outcome::Match,
+    Outcome::TimedOut,
+    Outcome::Exhausted,
+    Outcome::AlreadyMatched,
+    Outcome::Cancelled,
+];
+
+struct Histogram {
+    bounds: &'static [f64],
+    // observations at or below each bound, not yet cumulative
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(i) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[i] += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn write(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "histogram");
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter()) {
+            cumulative += count;
+            writeln!(
+                out,
+                "{}_{}_bucket{{le=\"{}\"}} {}",
+                PREFIX, name, bound, cumulative
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "{}_{}_bucket{{le=\"+Inf\"}} {}",
+            PREFIX, name, self.count
+        )
+        .unwrap();
+        writeln!(out, "{}_{}_sum {}", PREFIX, name, self.sum).unwrap();
+        writeln!(out, "{}_{}_count {}", PREFIX, name, self.count).unwrap();
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    writeln!(out, "# HELP {}_{} {}", PREFIX, name, help).unwrap();
+    writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind).unwrap();
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "counter");
+    writeln!(out, "{}_{} {}", PREFIX, name, value).unwrap();
+}
+
+fn outcome_index(outcome: Outcome) -> usize {
+    OUTCOMES.iter().position(|o| *o == outcome).unwrap()
+}
+
+// an outcome as a label value
+fn outcome_label(outcome: Outcome) -> String {
+    outcome.to_string().replace(' ', "_")
+}
+
+struct Registry {
+    generations: u64,
+    moves: u64,
+    searches: [u64; OUTCOMES.len()],
+    recognition_failures: u64,
+    score: Option<u64>,
+    search_seconds: Histogram,
+    explored: Histogram,
+    path_length: Histogram,
+    capture_seconds: Histogram,
+}
+
+impl Registry {
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        counter(
+            &mut out,
+            "generations_total",
+            "Paths played.",
+            self.generations,
+        );
+        counter(
+            &mut out,
+            "moves_total",
+            "Moves sent to the game.",
+            self.moves,
+        );
+        header(
+            &mut out,
+            "searches_total",
+            "Searches by how they ended; rate of outcome=\"match\" is matches found.",
+            "counter",
+        );
+        for outcome in OUTCOMES.iter() {
+            writeln!(
+                out,
+                "{}_searches_total{{outcome=\"{}\"}} {}",
+                PREFIX,
+                outcome_label(*outcome),
+                self.searches[outcome_index(*outcome)]
+            )
+            .unwrap();
+        }
+        counter(
+            &mut out,
+            "recognition_failures_total",
+            "Frames captured mid run that held no readable board.",
+            self.recognition_failures,
+        );
+        if let Some(score) = self.score {
+            header(&mut out, "score", "Last score read from the game.", "gauge");
+            writeln!(out, "{}_score {}", PREFIX, score).unwrap();
+        }
+
+        self.search_seconds.write(
+            &mut out,
+            "search_duration_seconds",
+            "Time the bot waited on each search.",
+        );
+        self.explored.write(
+            &mut out,
+            "search_explored_boards",
+            "Boards explored by each search.",
+        );
+        self.path_length
+            .write(&mut out, "path_length_moves", "Moves in each path played.");
+        self.capture_seconds.write(
+            &mut out,
+            "capture_duration_seconds",
+            "Time to capture and convert each image.",
+        );
+        out
+    }
+}
+
+// counters and histograms of a run, served in the Prometheus text format on
+// /metrics at a local address
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+fn serve(mut stream: TcpStream, registry: &Mutex<Registry>) -> io::Result<()> {
+    match http::read_path(&stream)?.as_str() {
+        "/metrics" => {
+            let body = registry.lock().unwrap().render();
+            http::respond(&mut stream, "200 OK", "text/plain; version=0.0.4", &body)
+        }
+        _ => http::not_found(&mut stream),
+    }
+}
+
+impl Metrics {
+    pub fn start(address: SocketAddr) -> io::Result<Metrics> {
+        let registry = Arc::new(Mutex::new(Registry {
+            generations: 0,
+            moves: 0,
+            searches: [0; OUTCOMES.len()],
+            recognition_failures: 0,
+            score: None,
+            search_seconds: Histogram::new(&SEARCH_SECONDS_BUCKETS),
+            explored: Histogram::new(&EXPLORED_BUCKETS),
+            path_length: Histogram::new(&PATH_LENGTH_BUCKETS),
+            capture_seconds: Histogram::new(&CAPTURE_SECONDS_BUCKETS),
+        }));
+
+        let served = registry.clone();
+        http::listen(address, move |stream| serve(stream, &served))?;
+        Ok(Metrics { registry })
+    }
+
+    pub fn record_search(&self, search: &Search, waited: Duration) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.searches[outcome_index(search.outcome)] += 1;
+        registry.search_seconds.observe(waited.as_secs_f64());
+        registry.explored.observe(search.explored as f64);
+    }
+
+    pub fn record_path(&self, moves: usize) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.generations += 1;
+        registry.moves += moves as u64;
+        registry.path_length.observe(moves as f64);
+    }
+
+    pub fn record_score(&self, score: Option<u64>) {
+        if score.is_some() {
+            self.registry.lock().unwrap().score = score;
+        }
+    }
+
+    pub fn record_recognition_failures(&self, count: usize) {
+        self.registry.lock().unwrap().recognition_failures += count as u64;
+    }
+
+    pub fn record_captures(&self, timings: &[Duration]) {
+        let mut registry = self.registry.lock().unwrap();
+        for timing in timings {
+            registry.capture_seconds.observe(timing.as_secs_f64());
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use crate::board::{Board, Move, MAX_COLS, MAX_ROWS};
+use crate::error::{Error, Result};
+use crate::render;
+use crate::screen;
+use crate::screen::{
+    Confidence, BOARD_PIXEL_HEIGHT, BOARD_PIXEL_WIDTH, ITEM_SIZE, WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+
+use x11rb::connection::Connection;
+use x11rb::properties::{WmSizeHints, WmSizeHintsSpecification};
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+const TITLE: &[u8] = b"hack_match_bot overlay";
+const FONT: &[u8] = b"fixed";
+
+// room under the board for one line of arrows per move
+const PATH_HEIGHT: u16 = 200;
+const MOVE_SPACING: i16 = 12;
+const ARROW_HEAD: i16 = 4;
+
+// 24-bit TrueColor pixel values
+const BLACK: u32 = 0x000000;
+const GRID: u32 = 0x505050;
+const PHAGE: u32 = 0x40ff40;
+const DOUBT: u32 = 0xff4040;
+const PATH: u32 = 0xffff40;
+
+const WIDTH: u16 = BOARD_PIXEL_WIDTH as u16;
+const HEIGHT: u16 = BOARD_PIXEL_HEIGHT as u16 + PATH_HEIGHT;
+// where the overlay goes inside the game window when there is no room beside
+// it: against the right edge, clear of the board and the HUD so it is never
+// captured with them
+const INSIDE_X: i16 = (WINDOW_WIDTH - WIDTH) as i16;
+const INSIDE_Y: i16 = ((WINDOW_HEIGHT - HEIGHT) / 2) as i16;
+
+const SIZE: i16 = ITEM_SIZE as i16;
+const PHAGE_TOP: i16 = (MAX_ROWS * ITEM_SIZE) as i16;
+
+// right of the game window at game, else left of it, else inside it
+fn placement(screen: &Screen, (x, y): (i16, i16)) -> (i16, i16) {
+    let right = i32::from(x) + i32::from(WINDOW_WIDTH);
+    if right + i32::from(WIDTH) <= i32::from(screen.width_in_pixels) {
+        (right as i16, y)
+    } else if i32::from(x) >= i32::from(WIDTH) {
+        (x - WIDTH as i16, y)
+    } else {
+        (x + INSIDE_X, y + INSIDE_Y)
+    }
+}
+
+// a window beside the game showing the board as it was read, the grid it was
+// read on, how sure each empty cell is, and the path about to be played
+pub struct Overlay {
+    window: Window,
+    gc: Gcontext,
+    depth: u8,
+}
+
+fn segment(x1: i16, y1: i16, x2: i16, y2: i16) -> Segment {
+    Segment { x1, y1, x2, y2 }
+}
+
+// an arrow from (x1, y) to (x2, y)
+fn horizontal_arrow(x1: i16, x2: i16, y: i16) -> Vec<Segment> {
+    let back = if x2 > x1 { -ARROW_HEAD } else { ARROW_HEAD };
+    vec![
+        segment(x1, y, x2, y),
+        segment(x2, y, x2 + back, y - ARROW_HEAD),
+        segment(x2, y, x2 + back, y + ARROW_HEAD),
+    ]
+}
+
+// an upward arrow through (x, y), pointing both ways if both is set
+fn vertical_arrow(x: i16, y: i16, both: bool) -> Vec<Segment> {
+    let (top, bottom) = (y - ARROW_HEAD, y + ARROW_HEAD);
+    let mut segments = vec![
+        segment(x, bottom, x, top),
+        segment(x, top, x - ARROW_HEAD, y),
+        segment(x, top, x + ARROW_HEAD, y),
+    ];
+    if both {
+        segments.push(segment(x, bottom, x - ARROW_HEAD, y));
+        segments.push(segment(x, bottom, x + ARROW_HEAD, y));
+    }
+    segments
+}
+
+impl Overlay {
+    pub fn new<Conn: Connection>(conn: &Conn, screen: &Screen, game: Window) -> Result<Overlay> {
+        if screen.root_depth != 24 {
+            return Err(Error::OverlayDepth(screen.root_depth));
+        }
+
+        let (x, y) = placement(screen, screen::window_position(conn, screen.root, game)?);
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            screen.root,
+            x,
+            y,
+            WIDTH,
+            HEIGHT,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().background_pixel(screen.black_pixel),
+        )?;
+        // window managers place new windows where they like unless asked
+        let mut hints = WmSizeHints::new();
+        hints.position = Some((
+            WmSizeHintsSpecification::UserSpecified,
+            i32::from(x),
+            i32::from(y),
+        ));
+        hints.set_normal_hints(conn, window)?;
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            TITLE,
+        )?;
+
+        let font = conn.generate_id()?;
+        conn.open_font(font, FONT)?.check()?;
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, window, &CreateGCAux::new().font(font))?;
+        conn.close_font(font)?;
+
+        conn.map_window(window)?;
+        conn.flush()?;
+
+        Ok(Overlay {
+            window,
+            gc,
+            depth: screen.root_depth,
+        })
+    }
+
+    // keeps the overlay in sight when the game window is raised over it
+    pub fn raise<Conn: Connection>(&self, conn: &Conn) -> Result<()> {
+        let config = ConfigureWindowAux::new().stack_mode(StackMode::ABOVE);
+        conn.configure_window(self.window, &config)?.check()?;
+        Ok(())
+    }
+
+    fn color<Conn: Connection>(&self, conn: &Conn, pixel: u32) -> Result<()> {
+        conn.change_gc(self.gc, &ChangeGCAux::new().foreground(pixel))?;
+        Ok(())
+    }
+
+    fn draw_path<Conn: Connection>(&self, conn: &Conn, board: &Board, path: &[Move]) -> Result<()> {
+        let mut col = board.phage_col() as i16;
+        let mut segments = Vec::new();
+
+        let lines = (PATH_HEIGHT as i16 - MOVE_SPACING) / MOVE_SPACING;
+        for (i, m) in path.iter().take(lines as usize).enumerate() {
+            let y = BOARD_PIXEL_HEIGHT as i16 + MOVE_SPACING * (i as i16 + 1);
+            let x = col * SIZE + SIZE / 2;
+            match m {
+                Move::Left if col > 0 => {
+                    segments.extend(horizontal_arrow(x, x - SIZE, y));
+                    col -= 1;
+                }
+                Move::Right if col < MAX_COLS as i16 - 1 => {
+                    segments.extend(horizontal_arrow(x, x + SIZE, y));
+                    col += 1;
+                }
+                Move::Exchange => segments.extend(vertical_arrow(x, y, false)),
+                Move::Swap => segments.extend(vertical_arrow(x, y, true)),
+                // a move into the wall does nothing
+                _ => (),
+            }
+        }
+
+        self.color(conn, PATH)?;
+        conn.poly_segment(self.window, self.gc, &segments)?;
+        Ok(())
+    }
+
+    pub fn show<Conn: Connection>(
+        &self,
+        conn: &Conn,
+        board: &Board,
+        confidence: &Confidence,
+        y_offset: usize,
+        path: &[Move],
+    ) -> Result<()> {
+        render::put_board(
+            conn,
+            self.window,
+            self.gc,
+            self.depth,
+            0,
+            0,
+            &render::render(board),
+        )?;
+
+        self.color(conn, BLACK)?;
+        conn.poly_fill_rectangle(
+            self.window,
+            self.gc,
+            &[Rectangle {
+                x: 0,
+                y: BOARD_PIXEL_HEIGHT as i16,
+                width: BOARD_PIXEL_WIDTH as u16,
+                height: PATH_HEIGHT,
+            }],
+        )?;
+
+        // rows are drawn through the lines they were sampled on, which move
+        // down with the stack as it scrolls
+        let sample = |row: usize| (row * ITEM_SIZE + y_offset) as i16;
+        let mut grid = Vec::new();
+        for col in 1..MAX_COLS as i16 {
+            grid.push(segment(col * SIZE, 0, col * SIZE, PHAGE_TOP));
+        }
+        for row in 0..MAX_ROWS {
+            grid.push(segment(
+                0,
+                sample(row),
+                BOARD_PIXEL_WIDTH as i16,
+                sample(row),
+            ));
+        }
+        self.color(conn, GRID)?;
+        conn.poly_segment(self.window, self.gc, &grid)?;
+
+        self.color(conn, PHAGE)?;
+        conn.poly_rectangle(
+            self.window,
+            self.gc,
+            &[Rectangle {
+                x: board.phage_col() as i16 * SIZE,
+                y: PHAGE_TOP,
+                width: ITEM_SIZE as u16 - 1,
+                height: (BOARD_PIXEL_HEIGHT - MAX_ROWS * ITEM_SIZE) as u16 - 1,
+            }],
+        )?;
+
+        self.color(conn, DOUBT)?;
+        for row in 0..MAX_ROWS {
+            for col in 0..MAX_COLS {
+                if confidence[row][col] < 1.0 {
+                    let text = format!("{:.0}%", confidence[row][col] * 100.0);
+                    conn.image_text8(
+                        self.window,
+                        self.gc,
+                        (col * ITEM_SIZE) as i16 + 8,
+                        sample(row) + SIZE / 2,
+                        text.as_bytes(),
+                    )?;
+                }
+            }
+        }
+
+        self.draw_path(conn, board, path)?;
+        conn.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+use crate::error::{Error, Result};
+
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::xproto::*;
+
+// the recognizer works on 4 bytes per pixel in blue, green, red, unused
+// order, which is what a 24-bit little endian X server sends as is
+pub const BYTES_PER_PIXEL: usize = 4;
+
+// how the X server lays out the window's pixels in a Z_PIXMAP image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PixelFormat {
+    depth: u8,
+    bits_per_pixel: u8,
+    scanline_pad: u8,
+    byte_order: ImageOrder,
+    red_mask: u32,
+    green_mask: u32,
+    blue_mask: u32,
+}
+
+// a captured region converted to the recognizer's layout
+#[derive(PartialEq)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    // the bits of each channel that survived the server's format
+    mask: u8,
+}
+
+impl Frame {
+    // data already in the recognizer's layout
+    pub fn native(data: Vec<u8>) -> Frame {
+        Frame { data, mask: 0xff }
+    }
+
+    // whether the pixels at offset are reference, as far as the format can tell
+    pub fn matches(&self, offset: usize, reference: &[u8]) -> bool {
+        self.data[offset..offset + reference.len()]
+            .iter()
+            .zip(reference.iter())
+            .all(|(a, b)| a & self.mask == b & self.mask)
+    }
+}
+
+fn channel_bits(mask: u32) -> u32 {
+    mask.count_ones()
+}
+
+// scales a channel of the pixel value to 8 bits, dropping low bits of wider
+// channels and leaving narrower ones with zeroes at the bottom
+fn channel(pixel: u32, mask: u32) -> u8 {
+    let bits = channel_bits(mask);
+    let value = (pixel & mask) >> mask.trailing_zeros();
+    if bits >= 8 {
+        (value >> (bits - 8)) as u8
+    } else {
+        (value << (8 - bits)) as u8
+    }
+}
+
+impl PixelFormat {
+    pub fn from_window<Conn: ?Sized + RequestConnection>(
+        conn: &Conn,
+        setup: &Setup,
+        screen: &Screen,
+        window: Window,
+    ) -> Result<PixelFormat> {
+        let depth = conn.get_geometry(window)?.reply()?.depth;
+        let visual_id = conn.get_window_attributes(window)?.reply()?.visual;
+
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .find(|d| d.depth == depth)
+            .and_then(|d| d.visuals.iter().find(|v| v.visual_id == visual_id))
+            .ok_or_else(|| Error::UnsupportedVisual(format!("unknown visual {:#x}", visual_id)))?;
+        if visual.class != VisualClass::TRUE_COLOR && visual.class != VisualClass::DIRECT_COLOR {
+            return Err(Error::UnsupportedVisual(format!(
+                "{:?} visual",
+                visual.class
+            )));
+        }
+
+        let pixmap_format = setup
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == depth)
+            .ok_or_else(|| {
+                Error::UnsupportedVisual(format!("no image format for depth {}", depth))
+            })?;
+
+        let format = PixelFormat {
+            depth,
+            bits_per_pixel: pixmap_format.bits_per_pixel,
+            scanline_pad: pixmap_format.scanline_pad,
+            byte_order: setup.image_byte_order,
+            red_mask: visual.red_mask,
+            green_mask: visual.green_mask,
+            blue_mask: visual.blue_mask,
+        };
+
+        if ![16, 24, 32].contains(&format.bits_per_pixel) {
+            return Err(Error::UnsupportedVisual(format!(
+                "{} bits per pixel",
+                format.bits_per_pixel
+            )));
+        }
+        if [format.red_mask, format.green_mask, format.blue_mask].contains(&0) {
+            return Err(Error::UnsupportedVisual(format!(
+                "color masks {:#x} {:#x} {:#x}",
+                format.red_mask, format.green_mask, format.blue_mask
+            )));
+        }
+
+        Ok(format)
+    }
+
+    // the format the recognizer was written for, which needs no conversion
+    fn is_native(&self) -> bool {
+        self.depth == 24
+            && self.bits_per_pixel == 32
+            && self.byte_order == ImageOrder::LSB_FIRST
+            && (self.red_mask, self.green_mask, self.blue_mask) == (0xff0000, 0xff00, 0xff)
+    }
+
+    // bytes in a width by height image in this format
+    pub fn image_size(&self, width: u16, height: u16) -> usize {
+        self.stride(width) * height as usize
+    }
+
+    fn stride(&self, width: u16) -> usize {
+        let pad = self.scanline_pad as usize;
+        let bits = width as usize * self.bits_per_pixel as usize;
+        bits.div_ceil(pad) * pad / 8
+    }
+
+    fn mask(&self) -> u8 {
+        let bits = [self.red_mask, self.green_mask, self.blue_mask]
+            .iter()
+            .map(|mask| channel_bits(*mask))
+            .min()
+            .unwrap_or(8);
+        if bits >= 8 {
+            0xff
+        } else {
+            0xff << (8 - bits)
+        }
+    }
+
+    fn pixel(&self, bytes: &[u8]) -> u32 {
+        let mut pixel = 0;
+        for i in 0..bytes.len() {
+            let byte = match self.byte_order {
+                ImageOrder::LSB_FIRST => bytes[bytes.len() - 1 - i],
+                _ => bytes[i],
+            };
+            pixel = pixel << 8 | u32::from(byte);
+        }
+        pixel
+    }
+
+    // converts a width by height Z_PIXMAP image to the recognizer's layout
+    pub fn convert(&self, data: Vec<u8>, width: u16, height: u16) -> Frame {
+        if self.is_native() {
+            return Frame::native(data);
+        }
+
+        let bytes_per_pixel = self.bits_per_pixel as usize / 8;
+        let stride = self.stride(width);
+        let mut converted = Vec::with_capacity(BYTES_PER_PIXEL * width as usize * height as usize);
+
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let offset = y * stride + x * bytes_per_pixel;
+                let pixel = self.pixel(&data[offset..offset + bytes_per_pixel]);
+                converted.extend_from_slice(&[
+                    channel(pixel, self.blue_mask),
+                    channel(pixel, self.green_mask),
+                    channel(pixel, self.red_mask),
+                    0,
+                ]);
+            }
+        }
+
+        Frame {
+            data: converted,
+            mask: self.mask(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u16 = 3;
+    const HEIGHT: u16 = 2;
+
+    // blue, green, red, unused, including colors the recognizer looks for
+    const PIXELS: [[u8; 4]; 6] = [
+        [0, 0, 0, 0],
+        [255, 255, 255, 0],
+        [7, 27, 29, 0],
+        [45, 40, 3, 0],
+        [51, 4, 9, 0],
+        [200, 100, 50, 0],
+    ];
+
+    fn format(
+        depth: u8,
+        bits_per_pixel: u8,
+        byte_order: ImageOrder,
+        (red_mask, green_mask, blue_mask): (u32, u32, u32),
+    ) -> PixelFormat {
+        PixelFormat {
+            depth,
+            bits_per_pixel,
+            scanline_pad: 32,
+            byte_order,
+            red_mask,
+            green_mask,
+            blue_mask,
+        }
+    }
+
+    // the channel scaled from 8 bits into mask
+    fn encode_channel(value: u8, mask: u32) -> u32 {
+        let bits = channel_bits(mask);
+        let value = if bits >= 8 {
+            u32::from(value) << (bits - 8)
+        } else {
+            u32::from(value) >> (8 - bits)
+        };
+        value << mask.trailing_zeros()
+    }
+
+    // PIXELS laid out the way a server using format would send them, with
+    // every bit outside the color masks set unless the layout is passed
+    // through as is
+    fn encode(format: &PixelFormat) -> Vec<u8> {
+        let bytes_per_pixel = format.bits_per_pixel as usize / 8;
+        let masks = format.red_mask | format.green_mask | format.blue_mask;
+        let unused = if format.is_native() {
+            0
+        } else {
+            !masks & (u32::MAX >> (32 - format.bits_per_pixel as u32))
+        };
+
+        let mut data = vec![0; format.image_size(WIDTH, HEIGHT)];
+        for (i, [b, g, r, _]) in PIXELS.iter().enumerate() {
+            let pixel = encode_channel(*r, format.red_mask)
+                | encode_channel(*g, format.green_mask)
+                | encode_channel(*b, format.blue_mask)
+                | unused;
+            let (x, y) = (i % WIDTH as usize, i / WIDTH as usize);
+            let offset = y * format.stride(WIDTH) + x * bytes_per_pixel;
+            for byte in 0..bytes_per_pixel {
+                let shift = match format.byte_order {
+                    ImageOrder::LSB_FIRST => 8 * byte,
+                    _ => 8 * (bytes_per_pixel - 1 - byte),
+                };
+                data[offset + byte] = (pixel >> shift) as u8;
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn converts_every_format_to_the_recognizers_layout() {
+        let lsb = ImageOrder::LSB_FIRST;
+        let msb = ImageOrder::MSB_FIRST;
+        let formats = [
+            format(24, 32, lsb, (0xff0000, 0xff00, 0xff)),
+            format(24, 24, lsb, (0xff0000, 0xff00, 0xff)),
+            format(16, 16, lsb, (0xf800, 0x7e0, 0x1f)),
+            format(16, 16, msb, (0xf800, 0x7e0, 0x1f)),
+            format(30, 32, lsb, (0x3ff00000, 0xffc00, 0x3ff)),
+            format(32, 32, lsb, (0xff0000, 0xff00, 0xff)),
+            format(24, 32, msb, (0xff0000, 0xff00, 0xff)),
+            format(24, 32, lsb, (0xff, 0xff00, 0xff0000)),
+        ];
+
+        for format in formats.iter() {
+            let frame = format.convert(encode(format), WIDTH, HEIGHT);
+            assert_eq!(
+                frame.data.len(),
+                BYTES_PER_PIXEL * WIDTH as usize * HEIGHT as usize,
+                "{:?}",
+                format
+            );
+            for (i, pixel) in PIXELS.iter().enumerate() {
+                assert!(
+                    frame.matches(BYTES_PER_PIXEL * i, pixel),
+                    "{:?} pixel {}: {:?}",
+                    format,
+                    i,
+                    &frame.data[BYTES_PER_PIXEL * i..BYTES_PER_PIXEL * (i + 1)]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn narrow_channels_only_compare_their_high_bits() {
+        let format = format(16, 16, ImageOrder::LSB_FIRST, (0xf800, 0x7e0, 0x1f));
+        let frame = format.convert(encode(&format), WIDTH, HEIGHT);
+        assert!(frame.matches(BYTES_PER_PIXEL * 2, &[7, 27, 29, 0]));
+        assert!(frame.matches(BYTES_PER_PIXEL * 2, &[0, 24, 24, 0]));
+        assert!(!frame.matches(BYTES_PER_PIXEL * 2, &[8, 27, 29, 0]));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::screen::{HudAreas, KeyTiming};
+use log::warn;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// settings measured on this machine, kept between runs as `key = value` lines
+#[derive(Default)]
+pub struct Profile {
+    pub key_timing: KeyTiming,
+    pub hud_areas: HudAreas,
+}
+
+pub fn default_path() -> PathBuf {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(".config"),
+    };
+    config_dir.join("hack_match_bot").join("profile")
+}
+
+fn parse_millis(value: &str) -> Option<Duration> {
+    value.parse().ok().map(Duration::from_millis)
+}
+
+// x, y, width, height in window pixels
+fn parse_area(value: &str) -> Option<(i16, i16, u16, u16)> {
+    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+    match parts[..] {
+        [x, y, width, height] => Some((
+            x.parse().ok()?,
+            y.parse().ok()?,
+            width.parse().ok()?,
+            height.parse().ok()?,
+        )),
+        _ => None,
+    }
+}
+
+impl Profile {
+    // a missing profile is the defaults, a bad line is reported and skipped
+    pub fn load(path: &Path) -> Profile {
+        let mut profile = Profile::default();
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return profile,
+        };
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => {
+                    warn!("Ignoring profile line: {}", line);
+                    continue;
+                }
+            };
+
+            let known = match key {
+                "key_press_ms" => parse_millis(value).map(|t| profile.key_timing.press = t),
+                "key_release_ms" => parse_millis(value).map(|t| profile.key_timing.release = t),
+                "score_area" => parse_area(value).map(|a| profile.hud_areas.score = a),
+                "level_area" => parse_area(value).map(|a| profile.hud_areas.level = a),
+                _ => None,
+            };
+            if known.is_none() {
+                warn!("Ignoring profile line: {}", line);
+            }
+        }
+
+        profile
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let (score, level) = (self.hud_areas.score, self.hud_areas.level);
+        fs::write(
+            path,
+            format!(
+                "# written by hack_match_bot --calibrate\nkey_press_ms = {}\nkey_release_ms = {}\n\
+                 score_area = {},{},{},{}\nlevel_area = {},{},{},{}\n",
+                self.key_timing.press.as_millis(),
+                self.key_timing.release.as_millis(),
+                score.0,
+                score.1,
+                score.2,
+                score.3,
+                level.0,
+                level.1,
+                level.2,
+                level.3
+            ),
+        )
+    }
+}
@@ -0,0 +1,108 @@
+use crate::pixel::BYTES_PER_PIXEL;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// a capture of the game taken on this machine with --learn, in the
+// recognizer's layout
+pub struct Image {
+    pub data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+// references are kept beside the profile as binary PPM files, so what was
+// captured can be opened and checked by eye
+pub fn dir(profile: &Path) -> PathBuf {
+    profile.with_file_name("references")
+}
+
+pub fn save(path: &Path, image: &Image) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut contents = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+    for pixel in image.data.chunks(BYTES_PER_PIXEL) {
+        contents.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+    }
+    fs::write(path, contents)
+}
+
+fn invalid(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} is not a binary PPM image", path.display()),
+    )
+}
+
+pub fn load(path: &Path) -> io::Result<Image> {
+    let contents = fs::read(path)?;
+
+    // the header is four whitespace separated fields followed by a single
+    // whitespace byte before the pixels
+    let mut fields = vec![];
+    let mut start = None;
+    let mut pixels = None;
+    for (i, byte) in contents.iter().enumerate() {
+        if !byte.is_ascii_whitespace() {
+            start.get_or_insert(i);
+            continue;
+        }
+        if let Some(start) = start.take() {
+            fields.push(String::from_utf8_lossy(&contents[start..i]).into_owned());
+            if fields.len() == 4 {
+                pixels = Some(i + 1);
+                break;
+            }
+        }
+    }
+
+    let pixels = pixels.ok_or_else(|| invalid(path))?;
+    let number = |field: &str| field.parse::<usize>().map_err(|_| invalid(path));
+    if fields[0] != "P6" || number(&fields[3])? != 255 {
+        return Err(invalid(path));
+    }
+    let width = number(&fields[1])?;
+    let height = number(&fields[2])?;
+
+    let rgb = &contents[pixels..];
+    if rgb.len() != 3 * width * height {
+        return Err(invalid(path));
+    }
+    let mut data = Vec::with_capacity(BYTES_PER_PIXEL * width * height);
+    for pixel in rgb.chunks(3) {
+        data.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 0]);
+    }
+
+    Ok(Image {
+        data,
+        width,
+        height,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn images_round_trip_through_ppm() {
+        let image = Image {
+            data: (0..24)
+                .map(|i| if i % 4 == 3 { 0 } else { i * 10 })
+                .collect(),
+            width: 3,
+            height: 2,
+        };
+        let path = env::temp_dir().join(format!("hack_match_bot_ppm_{}", std::process::id()));
+        save(&path, &image).unwrap();
+        let loaded = load(&path);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.width, loaded.height), (3, 2));
+        assert_eq!(loaded.data, image.data);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::board::{Board, Color, Item, MAX_COLS, MAX_ROWS};
+use crate::error::Result;
+use crate::pixel::BYTES_PER_PIXEL;
+use crate::screen::*;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+
+// items are drawn as squares inside their ITEM_SIZE cell, leaving a gap the
+// recognizer never looks at
+const TILE_MARGIN: usize = 4;
+const TILE_SIZE: usize = 60;
+// the held item sits under the phage, cut off by the bottom of the board
+const HELD_TOP: usize = PHAGE_HELD_Y_OFFSET - 12;
+// matched items are boxed in white, the right side of the box being where the
+// recognizer looks for it
+const OUTLINE_LEFT: usize = 1;
+const OUTLINE_RIGHT: usize = PIXEL_X_OFFSET + PIXEL_MATCH_OFFSET;
+const OUTLINE_WIDTH: usize = 2;
+// rows of the board sent per PutImage, keeping requests under the size limit
+const STRIP_ROWS: usize = 64;
+
+fn put(image: &mut [u8], x: usize, y: usize, pixel: &[u8]) {
+    let offset = BYTES_PER_PIXEL * (BOARD_PIXEL_WIDTH * y + x);
+    image[offset..offset + BYTES_PER_PIXEL].copy_from_slice(pixel);
+}
+
+fn fill(image: &mut [u8], x: usize, y: usize, width: usize, height: usize, pixel: &[u8]) {
+    for y in y..y + height {
+        for x in x..x + width {
+            put(image, x, y, pixel);
+        }
+    }
+}
+
+// copies a row of pixels as given, for sprites the recognizer matches exactly
+fn blit(image: &mut [u8], x: usize, y: usize, pixels: &[u8]) {
+    let offset = BYTES_PER_PIXEL * (BOARD_PIXEL_WIDTH * y + x);
+    image[offset..offset + pixels.len()].copy_from_slice(pixels);
+}
+
+fn file_pixel(color: Color) -> &'static [u8] {
+    let data: &'static [u8] = match color {
+        Color::Yellow => &YELLOW_DATA,
+        Color::Cyan => &CYAN_DATA,
+        Color::Red => &RED_DATA,
+        Color::Pink => &PINK_DATA,
+        Color::Blue => &BLUE_DATA,
+    };
+    &data[..BYTES_PER_PIXEL]
+}
+
+fn bomb_pixel(color: Color) -> &'static [u8] {
+    match color {
+        Color::Yellow => &YELLOW_BOMB_PIXEL,
+        Color::Cyan => &CYAN_BOMB_PIXEL,
+        Color::Red => &RED_BOMB_PIXEL,
+        Color::Pink => &PINK_BOMB_PIXEL,
+        Color::Blue => &BLUE_BOMB_PIXEL,
+    }
+}
+
+fn draw_outline(image: &mut [u8], x: usize, y: usize, height: usize) {
+    let white = &MATCH_OUTLINE_DATA[..BYTES_PER_PIXEL];
+    let width = OUTLINE_RIGHT + OUTLINE_WIDTH - OUTLINE_LEFT;
+    fill(image, x + OUTLINE_LEFT, y, width, OUTLINE_WIDTH, white);
+    fill(
+        image,
+        x + OUTLINE_LEFT,
+        y + height,
+        width,
+        OUTLINE_WIDTH,
+        white,
+    );
+    fill(image, x + OUTLINE_LEFT, y, OUTLINE_WIDTH, height, white);
+    fill(image, x + OUTLINE_RIGHT, y, OUTLINE_WIDTH, height, white);
+}
+
+fn draw_item(image: &mut [u8], item: Item, x: usize, y: usize, height: usize) {
+    let pixel = match item {
+        Item::File(color) | Item::MatchedFile(color) => file_pixel(color),
+        Item::Bomb(color) | Item::MatchedBomb(color) => bomb_pixel(color),
+        Item::Empty => return,
+    };
+    fill(image, x + TILE_MARGIN, y, TILE_SIZE, height, pixel);
+
+    if item.is_matched() {
+        draw_outline(image, x, y, height);
+    }
+}
+
+// draws the board region of a game screenshot showing board, in the layout
+// screen.rs reads, with the stack settled at the top of its rows
+pub fn render(board: &Board) -> Vec<u8> {
+    let mut image = vec![0; BOARD_IMAGE_BYTES];
+
+    for row in 0..MAX_ROWS {
+        for col in 0..MAX_COLS {
+            let item = board.item(row, col);
+            draw_item(
+                &mut image,
+                item,
+                col * ITEM_SIZE,
+                row * ITEM_SIZE,
+                TILE_SIZE,
+            );
+        }
+    }
+
+    let col = board.phage_col();
+    blit(
+        &mut image,
+        col * ITEM_SIZE + PHAGE_SILVER_DATA_X_OFFSET,
+        PHAGE_SILVER_DATA_Y_OFFSET,
+        &PHAGE_SILVER_DATA,
+    );
+
+    // the pink of the phage shows when it is not holding anything
+    if board.held() == Item::Empty {
+        blit(
+            &mut image,
+            col * ITEM_SIZE + PHAGE_PINK_DATA_X_OFFSET,
+            PHAGE_PINK_DATA_Y_OFFSET,
+            &PHAGE_PINK_DATA,
+        );
+    } else {
+        draw_item(
+            &mut image,
+            board.held(),
+            col * ITEM_SIZE,
+            HELD_TOP,
+            BOARD_PIXEL_HEIGHT - HELD_TOP,
+        );
+    }
+
+    image
+}
+
+// draws a rendered board at (x, y) in a window of a 24-bit TrueColor visual
+pub fn put_board<Conn: Connection>(
+    conn: &Conn,
+    window: Window,
+    gc: Gcontext,
+    depth: u8,
+    x: i16,
+    y: i16,
+    image: &[u8],
+) -> Result<()> {
+    let row_bytes = BYTES_PER_PIXEL * BOARD_PIXEL_WIDTH;
+
+    for (i, strip) in image.chunks(STRIP_ROWS * row_bytes).enumerate() {
+        conn.put_image(
+            ImageFormat::Z_PIXMAP,
+            window,
+            gc,
+            BOARD_PIXEL_WIDTH as u16,
+            (strip.len() / row_bytes) as u16,
+            x,
+            y + (i * STRIP_ROWS) as i16,
+            0,
+            depth,
+            strip,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use crate::pixel::Frame;
+
+    // the same boards every run, so a failure can be reproduced
+    const SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+    const BOARDS: usize = 500;
+
+    // xorshift, enough to vary the boards without a dependency
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, below: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % below as u64) as usize
+        }
+
+        fn item(&mut self) -> Item {
+            let color = board::COLORS[self.next(board::COLORS.len())];
+            match self.next(4) {
+                0 => Item::Bomb(color),
+                1 => Item::MatchedFile(color),
+                2 => Item::MatchedBomb(color),
+                _ => Item::File(color),
+            }
+        }
+    }
+
+    fn random_board(random: &mut Random) -> Board {
+        let mut items = [[Item::Empty; MAX_COLS]; MAX_ROWS];
+        for col in 0..MAX_COLS {
+            for row in 0..random.next(MAX_ROWS + 1) {
+                items[row][col] = random.item();
+            }
+        }
+        // the board can only be placed on screen by the items in it
+        items[0][random.next(MAX_COLS)] = random.item();
+
+        // the phage cannot pick up matched items
+        let held = match random.next(3) {
+            0 => Item::Empty,
+            _ => random.item().to_normal(),
+        };
+        board::make_board(random.next(MAX_COLS), held, items)
+    }
+
+    #[test]
+    fn recognition_reads_back_rendered_boards() {
+        let mut random = Random(SEED);
+        let mut failures = vec![];
+
+        for _ in 0..BOARDS {
+            let board = random_board(&mut random);
+            let frame = Frame::native(render(&board));
+
+            match board_from_image(&frame) {
+                Ok((read, _)) if read == board => (),
+                Ok((read, _)) => failures.push(format!("Rendered:\n{}\nRead:\n{}", board, read)),
+                Err(e) => failures.push(format!("Rendered:\n{}\n{}", board, e)),
+            }
+        }
+
+        assert!(
+            failures.is_empty(),
+            "{} of {} boards read back wrong\n{}",
+            failures.len(),
+            BOARDS,
+            failures.join("\n")
+        );
+    }
+}
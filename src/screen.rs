@@ -1,128 +1,141 @@
 use crate::board;
 use crate::board::{Board, Color, Item, Move};
+use crate::capture::Capture;
+use crate::damage::DamageWatch;
+use crate::error::{Error, Result};
+use crate::hud::{Digits, Hud};
+use crate::pixel::{Frame, PixelFormat, BYTES_PER_PIXEL};
+use crate::reference;
+use crate::uinput;
+use log::{info, warn};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::{thread, time};
 
-use x11rb::connection::RequestConnection;
-use x11rb::image::*;
+use x11rb::connection::{Connection, RequestConnection};
 use x11rb::protocol::xproto::*;
 use x11rb::protocol::xtest;
 
-pub const KEY_DELAY_MILLIS: u64 = 17;
+const KEY_DELAY_MILLIS: u64 = 17;
 const KEY_DELAY: time::Duration = time::Duration::from_millis(KEY_DELAY_MILLIS);
 const RECHECK_WAIT_TIME: time::Duration = time::Duration::from_millis(KEY_DELAY_MILLIS + 3);
-
-const ITEM_SIZE: usize = 72;
-const BOARD_PIXEL_WIDTH: usize = board::MAX_COLS * ITEM_SIZE;
-const BOARD_PIXEL_HEIGHT: usize = 770;
+// the stack scrolls constantly while playing, so a still frame means it is not
+const STALL_TIME: time::Duration = time::Duration::from_millis(1000);
+const VERIFY_ATTEMPTS: usize = 2;
+// longest wait for a redraw, short enough to still notice a frozen screen
+const REDRAW_TIMEOUT: time::Duration = time::Duration::from_millis(100);
+
+pub const ITEM_SIZE: usize = 72;
+pub const BOARD_PIXEL_WIDTH: usize = board::MAX_COLS * ITEM_SIZE;
+pub const BOARD_PIXEL_HEIGHT: usize = 770;
 const BOARD_X_OFFSET: usize = 440;
 const BOARD_Y_OFFSET: usize = 151;
 const BOARD_PIXEL_HEIGHT_ITEMS: usize = 810 - BOARD_Y_OFFSET;
+pub const BOARD_REGION: (i16, i16, u16, u16) = (
+    BOARD_X_OFFSET as i16,
+    BOARD_Y_OFFSET as i16,
+    BOARD_PIXEL_WIDTH as u16,
+    BOARD_PIXEL_HEIGHT as u16,
+);
+pub const BOARD_IMAGE_BYTES: usize = BYTES_PER_PIXEL * BOARD_PIXEL_WIDTH * BOARD_PIXEL_HEIGHT;
 
-const PIXEL_X_OFFSET: usize = 30;
-const PIXEL_MATCH_OFFSET: usize = 507 - BOARD_X_OFFSET - PIXEL_X_OFFSET;
-
-const WINDOW_WIDTH: u16 = 1920;
-const WINDOW_HEIGHT: u16 = 1080;
+pub const PIXEL_X_OFFSET: usize = 30;
+pub const PIXEL_MATCH_OFFSET: usize = 507 - BOARD_X_OFFSET - PIXEL_X_OFFSET;
 
-// verified by validate_window()
-const BYTES_PER_PIXEL: usize = 4;
+pub const WINDOW_WIDTH: u16 = 1920;
+pub const WINDOW_HEIGHT: u16 = 1080;
 
 const XK_A: u32 = 0x0061;
 const XK_D: u32 = 0x0064;
 const XK_J: u32 = 0x006a;
 const XK_K: u32 = 0x006b;
-
-const YELLOW_BOMB_PIXEL: [u8; 4] = [7, 27, 29, 0];
-const CYAN_BOMB_PIXEL: [u8; 4] = [45, 40, 3, 0];
-const RED_BOMB_PIXEL: [u8; 4] = [15, 9, 66, 0];
-const PINK_BOMB_PIXEL: [u8; 4] = [50, 0, 60, 0];
-const BLUE_BOMB_PIXEL: [u8; 4] = [51, 4, 9, 0];
-
-const PHAGE_HELD_Y_OFFSET: usize = 908 - BOARD_Y_OFFSET;
-const PHAGE_PINK_DATA_X_OFFSET: usize = 31;
-const PHAGE_PINK_DATA_Y_OFFSET: usize = 886 - BOARD_Y_OFFSET;
-const PHAGE_SILVER_DATA_X_OFFSET: usize = 22;
-const PHAGE_SILVER_DATA_Y_OFFSET: usize = 833 - BOARD_Y_OFFSET;
+const XK_RETURN: u32 = 0xff0d;
+
+pub const YELLOW_BOMB_PIXEL: [u8; 4] = [7, 27, 29, 0];
+pub const CYAN_BOMB_PIXEL: [u8; 4] = [45, 40, 3, 0];
+pub const RED_BOMB_PIXEL: [u8; 4] = [15, 9, 66, 0];
+pub const PINK_BOMB_PIXEL: [u8; 4] = [50, 0, 60, 0];
+pub const BLUE_BOMB_PIXEL: [u8; 4] = [51, 4, 9, 0];
+
+pub const PHAGE_HELD_Y_OFFSET: usize = 908 - BOARD_Y_OFFSET;
+pub const PHAGE_PINK_DATA_X_OFFSET: usize = 31;
+pub const PHAGE_PINK_DATA_Y_OFFSET: usize = 886 - BOARD_Y_OFFSET;
+pub const PHAGE_SILVER_DATA_X_OFFSET: usize = 22;
+pub const PHAGE_SILVER_DATA_Y_OFFSET: usize = 833 - BOARD_Y_OFFSET;
 const PHAGE_CROUCH_X_OFFSET: usize = 3;
 const PHAGE_CROUCH_Y_OFFSET: usize = 9;
 
-const YELLOW_DATA: [u8; 32] = [
+pub const YELLOW_DATA: [u8; 32] = [
     24, 163, 235, 0, 24, 163, 235, 0, 24, 163, 235, 0, 24, 163, 235, 0, 24, 163, 235, 0, 24, 163,
     235, 0, 24, 163, 235, 0, 24, 163, 235, 0,
 ];
-const CYAN_DATA: [u8; 32] = [
+pub const CYAN_DATA: [u8; 32] = [
     156, 186, 18, 0, 156, 186, 18, 0, 156, 186, 18, 0, 156, 186, 18, 0, 156, 186, 18, 0, 156, 186,
     18, 0, 156, 186, 18, 0, 156, 186, 18, 0,
 ];
-const RED_DATA: [u8; 32] = [
+pub const RED_DATA: [u8; 32] = [
     49, 22, 220, 0, 49, 22, 220, 0, 49, 22, 220, 0, 49, 22, 220, 0, 49, 22, 220, 0, 49, 22, 220, 0,
     49, 22, 220, 0, 49, 22, 220, 0,
 ];
-const PINK_DATA: [u8; 32] = [
+pub const PINK_DATA: [u8; 32] = [
     184, 22, 251, 0, 184, 22, 251, 0, 184, 22, 251, 0, 184, 22, 251, 0, 184, 22, 251, 0, 184, 22,
     251, 0, 184, 22, 251, 0, 184, 22, 251, 0,
 ];
-const BLUE_DATA: [u8; 32] = [
+pub const BLUE_DATA: [u8; 32] = [
     130, 57, 32, 0, 130, 57, 32, 0, 130, 57, 32, 0, 130, 57, 32, 0, 130, 57, 32, 0, 130, 57, 32, 0,
     130, 57, 32, 0, 130, 57, 32, 0,
 ];
 
-const PHAGE_SILVER_DATA: [u8; 32] = [
+pub const PHAGE_SILVER_DATA: [u8; 32] = [
     255, 255, 228, 0, 255, 255, 228, 0, 255, 255, 229, 0, 255, 255, 229, 0, 255, 255, 229, 0, 255,
     255, 229, 0, 255, 255, 228, 0, 255, 255, 228, 0,
 ];
-const PHAGE_PINK_DATA: [u8; 32] = [
+pub const PHAGE_PINK_DATA: [u8; 32] = [
     148, 8, 221, 0, 148, 8, 221, 0, 149, 4, 222, 0, 150, 0, 224, 0, 150, 0, 224, 0, 150, 0, 224, 0,
     150, 0, 224, 0, 150, 0, 224, 0,
 ];
 
-const MATCH_OUTLINE_DATA: [u8; 8] = [255, 255, 255, 0, 255, 255, 255, 0];
-
-fn screenshot_game<Conn: ?Sized + RequestConnection>(conn: &Conn, window: Window) -> Vec<u8> {
-    conn.get_image(
-        ImageFormat::Z_PIXMAP,
-        window,
-        BOARD_X_OFFSET as i16,
-        BOARD_Y_OFFSET as i16,
-        BOARD_PIXEL_WIDTH as u16,
-        BOARD_PIXEL_HEIGHT as u16,
-        !0,
-    )
-    .unwrap()
-    .reply()
-    .unwrap()
-    .data
+pub const MATCH_OUTLINE_DATA: [u8; 8] = [255, 255, 255, 0, 255, 255, 255, 0];
+
+pub fn screenshot_game<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+) -> Result<Frame> {
+    capture.get_image(conn, window, BOARD_REGION)
 }
 
 fn coord_to_offset(x: usize, y: usize) -> usize {
     BYTES_PER_PIXEL * (BOARD_PIXEL_WIDTH * y + x)
 }
 
-fn item_from_data(data: &[u8], x: usize, y: usize) -> Item {
+fn item_from_data(frame: &Frame, x: usize, y: usize) -> Item {
     let offset = coord_to_offset(x, y);
     let match_offset = coord_to_offset(x + PIXEL_MATCH_OFFSET, y);
 
-    let matched = &data[match_offset..match_offset + 8] == &MATCH_OUTLINE_DATA;
+    let matched = frame.matches(match_offset, &MATCH_OUTLINE_DATA);
 
-    let item = if &data[offset..offset + 32] == &YELLOW_DATA {
+    let item = if frame.matches(offset, &YELLOW_DATA) {
         Item::File(Color::Yellow)
-    } else if &data[offset..offset + 32] == &CYAN_DATA {
+    } else if frame.matches(offset, &CYAN_DATA) {
         Item::File(Color::Cyan)
-    } else if &data[offset..offset + 32] == &RED_DATA {
+    } else if frame.matches(offset, &RED_DATA) {
         Item::File(Color::Red)
-    } else if &data[offset..offset + 32] == &PINK_DATA {
+    } else if frame.matches(offset, &PINK_DATA) {
         Item::File(Color::Pink)
-    } else if &data[offset..offset + 32] == &BLUE_DATA {
+    } else if frame.matches(offset, &BLUE_DATA) {
         Item::File(Color::Blue)
-    } else if &data[offset..offset + 4] == &YELLOW_BOMB_PIXEL {
+    } else if frame.matches(offset, &YELLOW_BOMB_PIXEL) {
         Item::Bomb(Color::Yellow)
-    } else if &data[offset..offset + 4] == &CYAN_BOMB_PIXEL {
+    } else if frame.matches(offset, &CYAN_BOMB_PIXEL) {
         Item::Bomb(Color::Cyan)
-    } else if &data[offset..offset + 4] == &RED_BOMB_PIXEL {
+    } else if frame.matches(offset, &RED_BOMB_PIXEL) {
         Item::Bomb(Color::Red)
-    } else if &data[offset..offset + 4] == &PINK_BOMB_PIXEL {
+    } else if frame.matches(offset, &PINK_BOMB_PIXEL) {
         Item::Bomb(Color::Pink)
-    } else if &data[offset..offset + 4] == &BLUE_BOMB_PIXEL {
+    } else if frame.matches(offset, &BLUE_BOMB_PIXEL) {
         Item::Bomb(Color::Blue)
     } else {
         Item::Empty
@@ -135,11 +148,11 @@ fn item_from_data(data: &[u8], x: usize, y: usize) -> Item {
     }
 }
 
-fn find_y_offset(data: &[u8]) -> Option<usize> {
+fn find_y_offset(frame: &Frame) -> Option<usize> {
     for y in (0..BOARD_PIXEL_HEIGHT_ITEMS).rev() {
         for i in 0..board::MAX_COLS {
             let x = i * ITEM_SIZE + PIXEL_X_OFFSET;
-            let item = item_from_data(data, x, y);
+            let item = item_from_data(frame, x, y);
             if item != Item::Empty {
                 return Some(y % ITEM_SIZE);
             }
@@ -148,31 +161,31 @@ fn find_y_offset(data: &[u8]) -> Option<usize> {
     None
 }
 
-fn find_phage_col(data: &[u8]) -> Option<usize> {
+pub fn find_phage_col(frame: &Frame) -> Option<usize> {
     for col in 0..board::MAX_COLS {
         let x = col * ITEM_SIZE + PHAGE_SILVER_DATA_X_OFFSET;
         let offset = coord_to_offset(x, PHAGE_SILVER_DATA_Y_OFFSET);
-        if &data[offset..offset + 32] == &PHAGE_SILVER_DATA {
+        if frame.matches(offset, &PHAGE_SILVER_DATA) {
             return Some(col);
         }
 
         let offset = coord_to_offset(x - PHAGE_CROUCH_X_OFFSET, PHAGE_SILVER_DATA_Y_OFFSET);
-        if &data[offset..offset + 32] == &PHAGE_SILVER_DATA {
+        if frame.matches(offset, &PHAGE_SILVER_DATA) {
             return Some(col);
         }
 
         let offset = coord_to_offset(x + PHAGE_CROUCH_X_OFFSET, PHAGE_SILVER_DATA_Y_OFFSET);
-        if &data[offset..offset + 32] == &PHAGE_SILVER_DATA {
+        if frame.matches(offset, &PHAGE_SILVER_DATA) {
             return Some(col);
         }
     }
     None
 }
 
-fn find_pink(data: &[u8], phage_col: usize) -> bool {
+fn find_pink(frame: &Frame, phage_col: usize) -> bool {
     let x = phage_col * ITEM_SIZE + PHAGE_PINK_DATA_X_OFFSET;
     let offset = coord_to_offset(x, PHAGE_PINK_DATA_Y_OFFSET);
-    if &data[offset..offset + 32] == &PHAGE_PINK_DATA {
+    if frame.matches(offset, &PHAGE_PINK_DATA) {
         return true;
     }
 
@@ -180,7 +193,7 @@ fn find_pink(data: &[u8], phage_col: usize) -> bool {
         x - PHAGE_CROUCH_X_OFFSET,
         PHAGE_PINK_DATA_Y_OFFSET + PHAGE_CROUCH_Y_OFFSET,
     );
-    if &data[offset..offset + 32] == &PHAGE_PINK_DATA {
+    if frame.matches(offset, &PHAGE_PINK_DATA) {
         return true;
     }
 
@@ -188,18 +201,18 @@ fn find_pink(data: &[u8], phage_col: usize) -> bool {
         x + PHAGE_CROUCH_X_OFFSET,
         PHAGE_PINK_DATA_Y_OFFSET + PHAGE_CROUCH_Y_OFFSET,
     );
-    if &data[offset..offset + 32] == &PHAGE_PINK_DATA {
+    if frame.matches(offset, &PHAGE_PINK_DATA) {
         return true;
     }
 
     false
 }
 
-fn find_held(data: &[u8], phage_col: usize) -> Option<Item> {
+fn find_held(frame: &Frame, phage_col: usize) -> Option<Item> {
     let held_x = phage_col * ITEM_SIZE + PIXEL_X_OFFSET;
-    let held = item_from_data(data, held_x, PHAGE_HELD_Y_OFFSET);
+    let held = item_from_data(frame, held_x, PHAGE_HELD_Y_OFFSET);
 
-    let found_pink = find_pink(data, phage_col);
+    let found_pink = find_pink(frame, phage_col);
 
     if found_pink == (held != Item::Empty) {
         None
@@ -208,18 +221,46 @@ fn find_held(data: &[u8], phage_col: usize) -> Option<Item> {
     }
 }
 
-pub fn get_board_from_window<Conn: ?Sized + RequestConnection>(
-    conn: &Conn,
-    window: Window,
-) -> Option<Board> {
-    let image_data = screenshot_game(conn, window);
+// how sure the reader is of each cell, from 0 to 1
+pub type Confidence = [[f32; board::MAX_COLS]; board::MAX_ROWS];
 
-    let y_offset = match find_y_offset(&image_data) {
-        Some(y) => y,
-        None => {
-            println!("Could not find board y offset");
-            return None;
+// files and bombs only read when every pixel matches, so they are certain; an
+// empty cell is less certain the more of a file color it shows
+pub fn read_confidence(frame: &Frame, y_offset: usize) -> Confidence {
+    let mut confidence = [[1.0; board::MAX_COLS]; board::MAX_ROWS];
+
+    for col in 0..board::MAX_COLS {
+        let x = col * ITEM_SIZE + PIXEL_X_OFFSET;
+        for row in 0..board::MAX_ROWS {
+            let y = row * ITEM_SIZE + y_offset;
+            if item_from_data(frame, x, y) != Item::Empty {
+                continue;
+            }
+
+            let offset = coord_to_offset(x, y);
+            let closest = [YELLOW_DATA, CYAN_DATA, RED_DATA, PINK_DATA, BLUE_DATA]
+                .iter()
+                .map(|data| {
+                    data.chunks(BYTES_PER_PIXEL)
+                        .enumerate()
+                        .filter(|(i, pixel)| frame.matches(offset + i * BYTES_PER_PIXEL, pixel))
+                        .count()
+                })
+                .max()
+                .unwrap_or(0);
+            confidence[row][col] =
+                1.0 - closest as f32 / (YELLOW_DATA.len() / BYTES_PER_PIXEL) as f32;
         }
+    }
+
+    confidence
+}
+
+// returns the board along with the stack's vertical offset within a row
+pub fn board_from_image(frame: &Frame) -> Result<(Board, usize)> {
+    let y_offset = match find_y_offset(frame) {
+        Some(y) => y,
+        None => return Err(Error::Recognition("could not find board y offset")),
     };
 
     let mut items = [[Item::Empty; board::MAX_COLS]; board::MAX_ROWS];
@@ -227,203 +268,834 @@ pub fn get_board_from_window<Conn: ?Sized + RequestConnection>(
         let x = col * ITEM_SIZE + PIXEL_X_OFFSET;
         for row in 0..board::MAX_ROWS {
             let y = row * ITEM_SIZE + y_offset;
-            items[row][col] = item_from_data(&image_data, x, y);
+            items[row][col] = item_from_data(frame, x, y);
         }
     }
 
-    let phage_col = match find_phage_col(&image_data) {
+    let phage_col = match find_phage_col(frame) {
         Some(col) => col,
-        None => {
-            println!("Could not find phage column");
-            return None;
-        }
+        None => return Err(Error::Recognition("could not find phage column")),
     };
 
-    let held = match find_held(&image_data, phage_col) {
+    let held = match find_held(frame, phage_col) {
         Some(h) => h,
-        None => {
-            println!("Could not read held item");
-            return None;
-        }
+        None => return Err(Error::Recognition("could not read held item")),
     };
 
-    Some(board::make_board(phage_col, held, items))
+    Ok((board::make_board(phage_col, held, items), y_offset))
+}
+
+// screens shown outside a run, recognized by comparing the board region with
+// a capture of each learned from the game with --learn
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Scene {
+    Title,
+    Pause,
+    GameOver,
+    // shown after a run that made the high score table, in place of the game
+    // over screen
+    HighScore,
+}
+
+// a sampled pixel counts as the same as the reference when every channel is
+// within SCENE_TOLERANCE, which also covers bits lost to the pixel format
+const SCENE_TOLERANCE: u8 = 24;
+const SCENE_SAMPLE_STEP: usize = 6;
+// share of sampled pixels that have to be the same, leaving room for
+// animations and the cursor
+const SCENE_MATCH_FRACTION: f64 = 0.9;
+
+impl Scene {
+    const ALL: [Scene; 4] = [
+        Scene::Title,
+        Scene::Pause,
+        Scene::GameOver,
+        Scene::HighScore,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Scene::Title => "title",
+            Scene::Pause => "pause",
+            Scene::GameOver => "gameover",
+            Scene::HighScore => "highscore",
+        }
+    }
+
+    fn reference_path(self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.ppm", self.name()))
+    }
+}
+
+impl FromStr for Scene {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Scene, ()> {
+        Scene::ALL
+            .iter()
+            .find(|scene| scene.name() == s)
+            .copied()
+            .ok_or(())
+    }
+}
+
+fn pixel_close(a: &[u8], b: &[u8]) -> bool {
+    a[..3]
+        .iter()
+        .zip(b[..3].iter())
+        .all(|(a, b)| a.abs_diff(*b) <= SCENE_TOLERANCE)
+}
+
+// whether the board region looks like reference, sampling a grid of pixels
+fn resembles(frame: &Frame, reference: &[u8]) -> bool {
+    let mut sampled = 0;
+    let mut same = 0;
+    for y in (0..BOARD_PIXEL_HEIGHT).step_by(SCENE_SAMPLE_STEP) {
+        for x in (0..BOARD_PIXEL_WIDTH).step_by(SCENE_SAMPLE_STEP) {
+            let offset = coord_to_offset(x, y);
+            let pixel = offset..offset + BYTES_PER_PIXEL;
+            sampled += 1;
+            if pixel_close(&frame.data[pixel.clone()], &reference[pixel]) {
+                same += 1;
+            }
+        }
+    }
+    same as f64 >= sampled as f64 * SCENE_MATCH_FRACTION
+}
+
+// the learned scenes, any without a reference are never recognized
+#[derive(Default)]
+pub struct Scenes {
+    references: Vec<(Scene, Vec<u8>)>,
+}
+
+impl Scenes {
+    pub fn load(dir: &Path) -> Scenes {
+        let mut scenes = Scenes::default();
+
+        for scene in Scene::ALL.iter() {
+            let path = scene.reference_path(dir);
+            match reference::load(&path) {
+                Ok(image)
+                    if (image.width, image.height) == (BOARD_PIXEL_WIDTH, BOARD_PIXEL_HEIGHT) =>
+                {
+                    scenes.references.push((*scene, image.data))
+                }
+                Ok(_) => warn!(
+                    "Ignoring {}, it is not the size of the board",
+                    path.display()
+                ),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => info!(
+                    "No {} screen learned, learn it with --learn {}",
+                    scene.name(),
+                    scene.name()
+                ),
+                Err(e) => warn!("Ignoring {} ({})", path.display(), e),
+            }
+        }
+
+        scenes
+    }
+
+    pub fn recognize(&self, frame: &Frame) -> Option<Scene> {
+        self.references
+            .iter()
+            .find(|(_, reference)| resembles(frame, reference))
+            .map(|(scene, _)| *scene)
+    }
+}
+
+// a counter in the HUD, read with digits learned from captures of it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Counter {
+    Score,
+    Level,
+}
+
+impl Counter {
+    const ALL: [Counter; 2] = [Counter::Score, Counter::Level];
+
+    fn name(self) -> &'static str {
+        match self {
+            Counter::Score => "score",
+            Counter::Level => "level",
+        }
+    }
+
+    fn reference_path(self, value: u64, dir: &Path) -> PathBuf {
+        dir.join(format!("{}-{}.ppm", self.name(), value))
+    }
+
+    // the counter and value a reference was saved for, from its file name
+    fn from_reference(path: &Path) -> Option<(Counter, u64)> {
+        let name = path.file_name()?.to_str()?.strip_suffix(".ppm")?;
+        let (counter, value) = name.split_once('-')?;
+        Some((counter.parse().ok()?, value.parse().ok()?))
+    }
+}
+
+// where the HUD counters are drawn in the window, as (x, y, width, height)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HudAreas {
+    pub score: (i16, i16, u16, u16),
+    pub level: (i16, i16, u16, u16),
+}
+
+impl HudAreas {
+    fn area(&self, counter: Counter) -> (i16, i16, u16, u16) {
+        match counter {
+            Counter::Score => self.score,
+            Counter::Level => self.level,
+        }
+    }
 }
 
-pub fn get_exapunks_window<Conn: ?Sized + RequestConnection>(
+impl Default for HudAreas {
+    // estimated from the layout around the board rather than measured from
+    // the game, set score_area and level_area in the profile where they miss
+    fn default() -> HudAreas {
+        HudAreas {
+            score: (1010, 190, 300, 40),
+            level: (1010, 300, 120, 40),
+        }
+    }
+}
+
+fn capture_counter<Conn: ?Sized + RequestConnection>(
     conn: &Conn,
+    capture: &Capture,
     window: Window,
-) -> Option<Window> {
-    let reply = conn
-        .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 64)
-        .unwrap()
-        .reply()
-        .unwrap();
+    area: (i16, i16, u16, u16),
+) -> Result<reference::Image> {
+    let frame = capture.get_image(conn, window, area)?;
+    Ok(reference::Image {
+        data: frame.data,
+        width: area.2 as usize,
+        height: area.3 as usize,
+    })
+}
+
+impl FromStr for Counter {
+    type Err = ();
 
-    let wm_name = String::from_utf8_lossy(&reply.value);
+    fn from_str(s: &str) -> std::result::Result<Counter, ()> {
+        Counter::ALL
+            .iter()
+            .find(|counter| counter.name() == s)
+            .copied()
+            .ok_or(())
+    }
+}
 
-    if wm_name == "EXAPUNKS" {
-        return Some(window);
+// the digits of every HUD capture learned so far
+fn load_digits(dir: &Path) -> Digits {
+    let mut digits = Digits::default();
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return digits,
+    };
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        // digits are matched by shape, so captures from before the areas were
+        // moved still teach them
+        let value = match Counter::from_reference(&path) {
+            Some((_, value)) => value,
+            None => continue,
+        };
+        let learned = match reference::load(&path) {
+            Ok(image) => digits.learn(&image, value),
+            Err(e) => {
+                warn!("Ignoring {} ({})", path.display(), e);
+                continue;
+            }
+        };
+        if !learned {
+            warn!("Ignoring {}, it does not show {}", path.display(), value);
+        }
     }
 
-    let children = conn.query_tree(window).unwrap().reply().unwrap().children;
+    digits
+}
 
-    for child in children {
-        match get_exapunks_window(conn, child) {
-            Some(win) => return Some(win),
-            None => (),
+// everything learned from the game with --learn
+pub struct Learned {
+    pub scenes: Scenes,
+    pub digits: Digits,
+}
+
+impl Learned {
+    pub fn load(dir: &Path) -> Learned {
+        let digits = load_digits(dir);
+        if digits.is_empty() {
+            info!("No HUD digits learned, learn them with --learn score=<SCORE>");
+        } else if !digits.missing().is_empty() {
+            info!(
+                "No HUD digits learned for {:?}, learn them with --learn score=<SCORE>",
+                digits.missing()
+            );
+        }
+
+        Learned {
+            scenes: Scenes::load(dir),
+            digits,
         }
     }
+}
 
-    None
+// reads the score and level, without capturing anything until some digits
+// have been learned
+pub fn read_hud<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+    areas: &HudAreas,
+    digits: &Digits,
+) -> Result<Hud> {
+    if digits.is_empty() {
+        return Ok(Hud::default());
+    }
+
+    let score = capture_counter(conn, capture, window, areas.score)?;
+    let level = capture_counter(conn, capture, window, areas.level)?;
+    Ok(Hud {
+        score: digits.read_number(&score),
+        level: digits.read_number(&level),
+    })
+}
+
+// saves the area of counter as a reference showing value, which has to be
+// what the game shows, returning where it went
+pub fn learn_counter<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+    areas: &HudAreas,
+    counter: Counter,
+    value: u64,
+    dir: &Path,
+) -> Result<PathBuf> {
+    let image = capture_counter(conn, capture, window, areas.area(counter))?;
+    if !Digits::default().learn(&image, value) {
+        return Err(Error::Learn(format!(
+            "the {} area does not show {} digits",
+            counter.name(),
+            value.to_string().len()
+        )));
+    }
+
+    let path = counter.reference_path(value, dir);
+    reference::save(&path, &image).map_err(Error::Reference)?;
+    Ok(path)
+}
+
+// saves the board region as the reference for scene, which has to be on
+// screen, returning where it went
+pub fn learn_scene<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+    scene: Scene,
+    dir: &Path,
+) -> Result<PathBuf> {
+    let frame = screenshot_game(conn, capture, window)?;
+    let path = scene.reference_path(dir);
+    reference::save(
+        &path,
+        &reference::Image {
+            data: frame.data,
+            width: BOARD_PIXEL_WIDTH,
+            height: BOARD_PIXEL_HEIGHT,
+        },
+    )
+    .map_err(Error::Reference)?;
+    Ok(path)
+}
+
+// the cheap part of validate_window, for checking on the window while playing
+pub fn check_geometry<Conn: ?Sized + RequestConnection>(conn: &Conn, window: Window) -> Result<()> {
+    let geometry = conn.get_geometry(window)?.reply()?;
+
+    if geometry.width != WINDOW_WIDTH || geometry.height != WINDOW_HEIGHT {
+        return Err(Error::WrongGeometry {
+            width: geometry.width,
+            height: geometry.height,
+        });
+    }
+
+    Ok(())
 }
 
+// the window's top left corner on the screen, for placing things beside it
+pub fn window_position<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    root: Window,
+    window: Window,
+) -> Result<(i16, i16)> {
+    let translated = conn.translate_coordinates(window, root, 0, 0)?.reply()?;
+    Ok((translated.dst_x, translated.dst_y))
+}
+
+// checks the window can be captured and played in, returning its pixel format
 pub fn validate_window<Conn: ?Sized + RequestConnection>(
     conn: &Conn,
     setup: &Setup,
     screen: &Screen,
     window: Window,
-) {
-    let geometry = conn.get_geometry(window).unwrap().reply().unwrap();
-
-    assert_eq!(geometry.width, WINDOW_WIDTH);
-    assert_eq!(geometry.height, WINDOW_HEIGHT);
-
-    let image_reply = conn
-        .get_image(
-            ImageFormat::Z_PIXMAP,
-            window,
-            0,
-            0,
-            WINDOW_WIDTH,
-            WINDOW_HEIGHT,
-            !0,
-        )
-        .unwrap()
-        .reply()
-        .unwrap();
-
-    let visual_id = image_reply.visual;
-
-    let image = Image::get_from_reply(setup, WINDOW_WIDTH, WINDOW_HEIGHT, image_reply).unwrap();
-
-    assert_eq!(image.scanline_pad(), ScanlinePad::Pad32);
-    assert_eq!(image.depth(), 24u8);
-    assert_eq!(image.bits_per_pixel(), BitsPerPixel::B32);
-
-    let visual_type = screen
-        .allowed_depths
-        .iter()
-        .find(|d| d.depth == image.depth())
-        .unwrap()
-        .visuals
-        .iter()
-        .find(|v| v.visual_id == visual_id)
-        .unwrap();
+) -> Result<PixelFormat> {
+    check_geometry(conn, window)?;
+
+    // fails while the window is not drawn
+    conn.get_image(ImageFormat::Z_PIXMAP, window, 0, 0, 1, 1, !0)?
+        .reply()?;
 
-    assert_eq!(visual_type.red_mask, 0x00ff0000);
-    assert_eq!(visual_type.green_mask, 0x0000ff00);
-    assert_eq!(visual_type.blue_mask, 0x000000ff);
+    PixelFormat::from_window(conn, setup, screen, window)
 }
 
-pub fn activate_window<Conn: ?Sized + RequestConnection>(conn: &Conn, window: Window) {
-    conn.set_input_focus(InputFocus::NONE, window, x11rb::CURRENT_TIME)
-        .unwrap()
-        .check()
-        .unwrap();
+pub fn activate_window<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    window: Window,
+) -> Result<()> {
+    conn.set_input_focus(InputFocus::NONE, window, x11rb::CURRENT_TIME)?
+        .check()?;
 
     let mut config = ConfigureWindowAux::new();
     config.stack_mode = Some(StackMode::ABOVE);
-    conn.configure_window(window, &config)
-        .unwrap()
-        .check()
-        .unwrap();
+    conn.configure_window(window, &config)?.check()?;
 
     // thread::sleep(time::Duration::from_millis(50));
+    Ok(())
 }
 
 fn keysym_to_keycode<Conn: ?Sized + RequestConnection>(
     conn: &Conn,
     setup: &Setup,
     keysym: Keysym,
-) -> Option<Keycode> {
+) -> Result<Keycode> {
     let mapping = conn
-        .get_keyboard_mapping(setup.min_keycode, setup.max_keycode - setup.min_keycode + 1)
-        .unwrap()
-        .reply()
-        .unwrap();
+        .get_keyboard_mapping(setup.min_keycode, setup.max_keycode - setup.min_keycode + 1)?
+        .reply()?;
 
     for (i, sym) in mapping.keysyms.iter().enumerate() {
         if sym == &keysym {
-            return Some(setup.min_keycode + (i / mapping.keysyms_per_keycode as usize) as u8);
+            return Ok(setup.min_keycode + (i / mapping.keysyms_per_keycode as usize) as u8);
         }
     }
 
-    None
+    Err(Error::NoKeycode(keysym))
+}
+
+// how long a key is held down, and how long to wait after releasing it
+#[derive(Copy, Clone, Debug)]
+pub struct KeyTiming {
+    pub press: time::Duration,
+    pub release: time::Duration,
+}
+
+impl KeyTiming {
+    pub fn move_time(&self) -> time::Duration {
+        self.press + self.release
+    }
+}
+
+impl Default for KeyTiming {
+    fn default() -> KeyTiming {
+        KeyTiming {
+            press: KEY_DELAY,
+            release: KEY_DELAY,
+        }
+    }
+}
+
+// where key presses are sent from
+#[derive(Debug)]
+pub enum Input {
+    Xtest,
+    Uinput(uinput::Keyboard),
+    // delivered straight to the game window, which then does not need focus
+    SendEvent { root: Window, window: Window },
 }
 
-pub fn get_keycodes<Conn: ?Sized + RequestConnection>(conn: &Conn, setup: &Setup) -> [Keycode; 4] {
-    let mut codes = [0; 4];
+#[derive(Debug)]
+pub struct Keys {
+    codes: [Keycode; 5],
+    input: Input,
+    pub timing: KeyTiming,
+}
 
-    for (i, sym) in [XK_A, XK_D, XK_K, XK_J].iter().enumerate() {
-        match keysym_to_keycode(conn, setup, *sym) {
-            Some(code) => codes[i] = code,
-            None => panic!("No keycode for keysym: {}", sym),
+impl Keys {
+    // for when the game window has been replaced by a new one
+    pub fn set_window(&mut self, new_window: Window) {
+        if let Input::SendEvent { window, .. } = &mut self.input {
+            *window = new_window;
         }
     }
+}
 
-    codes
+pub fn get_keys<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    setup: &Setup,
+    input: Input,
+    timing: KeyTiming,
+) -> Result<Keys> {
+    let mut codes = [0; 5];
+
+    for (i, sym) in [XK_A, XK_D, XK_K, XK_J, XK_RETURN].iter().enumerate() {
+        codes[i] = keysym_to_keycode(conn, setup, *sym)?;
+    }
+
+    Ok(Keys {
+        codes,
+        input,
+        timing,
+    })
 }
 
-fn send_key<Conn: ?Sized + RequestConnection>(conn: &Conn, key: Keycode) {
-    // opcodes found in xproto.h
-    // opcode for key press is 2
-    // opcode for key release is 3
-    xtest::fake_input(conn, 2, key, x11rb::CURRENT_TIME, x11rb::NONE, 0, 0, 0)
-        .unwrap()
-        .check()
-        .unwrap();
+fn set_key<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    keys: &Keys,
+    key: usize,
+    pressed: bool,
+) -> Result<()> {
+    match &keys.input {
+        Input::Xtest => {
+            // opcodes found in xproto.h
+            // opcode for key press is 2
+            // opcode for key release is 3
+            let opcode = if pressed { 2 } else { 3 };
+            xtest::fake_input(
+                conn,
+                opcode,
+                keys.codes[key],
+                x11rb::CURRENT_TIME,
+                x11rb::NONE,
+                0,
+                0,
+                0,
+            )?
+            .check()?;
+        }
+        Input::SendEvent { root, window } => {
+            let event = KeyPressEvent {
+                response_type: if pressed {
+                    KEY_PRESS_EVENT
+                } else {
+                    KEY_RELEASE_EVENT
+                },
+                detail: keys.codes[key],
+                sequence: 0,
+                time: x11rb::CURRENT_TIME,
+                root: *root,
+                event: *window,
+                child: x11rb::NONE,
+                root_x: 0,
+                root_y: 0,
+                event_x: 0,
+                event_y: 0,
+                state: 0,
+                same_screen: true,
+            };
+            let mask = if pressed {
+                EventMask::KEY_PRESS
+            } else {
+                EventMask::KEY_RELEASE
+            };
+            conn.send_event(false, *window, mask, event)?.check()?;
+        }
+        Input::Uinput(keyboard) => keyboard
+            .set_key(uinput::KEY_CODES[key], pressed)
+            .map_err(Error::Uinput)?,
+    }
 
-    thread::sleep(KEY_DELAY);
+    Ok(())
+}
 
-    xtest::fake_input(conn, 3, key, x11rb::CURRENT_TIME, x11rb::NONE, 0, 0, 0)
-        .unwrap()
-        .check()
-        .unwrap();
+// key is an index into Keys::codes
+fn send_key<Conn: ?Sized + RequestConnection>(conn: &Conn, keys: &Keys, key: usize) -> Result<()> {
+    set_key(conn, keys, key, true)?;
+    thread::sleep(keys.timing.press);
+    set_key(conn, keys, key, false)?;
+    thread::sleep(keys.timing.release);
+    Ok(())
+}
 
-    thread::sleep(KEY_DELAY);
+pub fn send_move<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    keys: &Keys,
+    m: Move,
+) -> Result<()> {
+    let key = match m {
+        Move::Left => 0,     // a
+        Move::Right => 1,    // d
+        Move::Swap => 2,     // k
+        Move::Exchange => 3, // j
+    };
+    send_key(conn, keys, key)
 }
 
 pub fn play_path<Conn: ?Sized + RequestConnection>(
     conn: &Conn,
-    codes: &[Keycode],
+    keys: &Keys,
     path: Vec<Move>,
-) {
+) -> Result<()> {
     for m in path {
-        match m {
-            Move::Left => send_key(conn, codes[0]),     // a
-            Move::Right => send_key(conn, codes[1]),    // d
-            Move::Swap => send_key(conn, codes[2]),     // k
-            Move::Exchange => send_key(conn, codes[3]), // j
+        send_move(conn, keys, m)?;
+    }
+    Ok(())
+}
+
+// plays the path, checking the screen against the prediction after every
+// verify_every moves and at the end, and stops at the first check that fails,
+// returning what is actually on screen so it can be replanned from
+pub fn play_path_verified<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    capture: &Capture,
+    window: Window,
+    keys: &Keys,
+    board: &Board,
+    path: Vec<Move>,
+    verify_every: usize,
+) -> Result<(Option<Reading>, usize)> {
+    let mut failed_reads = 0;
+    for (i, m) in path.iter().enumerate() {
+        send_move(conn, keys, *m)?;
+
+        let played = i + 1;
+        if played % verify_every != 0 && played != path.len() {
+            continue;
+        }
+
+        let expected = board.predict(&path[..played]);
+        let mut diverged = None;
+        // the game can be a frame behind the last key, so a mismatch only
+        // counts once a second look agrees
+        for _ in 0..VERIFY_ATTEMPTS {
+            let capture_time = time::Instant::now();
+            let frame = screenshot_game(conn, capture, window)?;
+            diverged = match board_from_image(&frame) {
+                Ok((actual, y_offset)) if actual != expected => Some((
+                    actual,
+                    Box::new(read_confidence(&frame, y_offset)),
+                    y_offset,
+                    capture_time,
+                )),
+                Ok(_) => None,
+                // an unreadable frame cannot tell whether the path diverged
+                Err(_) => {
+                    failed_reads += 1;
+                    None
+                }
+            };
+            if diverged.is_none() {
+                break;
+            }
+            thread::sleep(RECHECK_WAIT_TIME);
+        }
+
+        if let Some((board, confidence, y_offset, time)) = diverged {
+            warn!(
+                "Board diverged from prediction after move {} ({})",
+                played, m
+            );
+            let reading = Reading {
+                board,
+                confidence,
+                y_offset,
+                time,
+            };
+            return Ok((Some(reading), failed_reads));
+        }
+    }
+
+    Ok((None, failed_reads))
+}
+
+pub fn press_start<Conn: ?Sized + RequestConnection>(conn: &Conn, keys: &Keys) -> Result<()> {
+    send_key(conn, keys, 4) // return
+}
+
+pub struct Reading {
+    pub board: Board,
+    pub confidence: Box<Confidence>,
+    // offset of the stack within a row, grows as the stack scrolls down and
+    // wraps when a new row comes in
+    pub y_offset: usize,
+    pub time: time::Instant,
+}
+
+pub enum Observation {
+    // a readable board different from the previous one
+    NewBoard(Reading),
+    // the board is readable but nothing on screen has moved for STALL_TIME
+    Frozen,
+    // no board could be read for STALL_TIME, with the last reason why
+    NoBoard(Error),
+    // one of the learned scenes
+    Title,
+    Pause,
+    GameOver,
+}
+
+impl From<Scene> for Observation {
+    fn from(scene: Scene) -> Observation {
+        match scene {
+            Scene::Title => Observation::Title,
+            Scene::Pause => Observation::Pause,
+            Scene::GameOver | Scene::HighScore => Observation::GameOver,
         }
     }
 }
 
-// checks that the board is seen twice in a row and is different from the given board
-pub fn get_new_board<Conn: ?Sized + RequestConnection>(
+// with a damage watch, waits for the game to draw into the board, otherwise
+// sleeps for fallback
+fn wait_for_redraw<Conn: ?Sized + Connection>(
+    conn: &Conn,
+    damage: Option<&DamageWatch>,
+    fallback: time::Duration,
+) {
+    match damage {
+        Some(damage) => {
+            damage.wait(conn, BOARD_REGION, REDRAW_TIMEOUT);
+        }
+        None => thread::sleep(fallback),
+    }
+}
+
+// waits for a board different from the given board or one of the learned
+// screens, giving up after STALL_TIME without either so the caller can tell
+// the game is no longer being played, along with how many frames on the way
+// held no readable board
+pub fn observe<Conn: ?Sized + Connection>(
+    conn: &Conn,
+    capture: &Capture,
+    damage: Option<&DamageWatch>,
+    window: Window,
+    scenes: &Scenes,
+    prev_board: &Board,
+) -> Result<(Observation, usize)> {
+    let observation = watch_frames(conn, capture, damage, window, scenes, prev_board);
+    // the game keeps drawing between observations, with nothing waiting on the
+    // damage it reports
+    if let Some(damage) = damage {
+        damage.clear(conn);
+    }
+    observation
+}
+
+fn watch_frames<Conn: ?Sized + Connection>(
     conn: &Conn,
+    capture: &Capture,
+    damage: Option<&DamageWatch>,
     window: Window,
+    scenes: &Scenes,
     prev_board: &Board,
-) -> Board {
-    // let mut failed = 0;
+) -> Result<(Observation, usize)> {
+    let start = time::Instant::now();
+    let mut last_frame = None;
+    let mut unchanged_since = start;
+    let mut failed_reads = 0;
+
     loop {
-        match get_board_from_window(conn, window) {
-            Some(board) => {
+        let capture_time = time::Instant::now();
+        let frame = screenshot_game(conn, capture, window)?;
+        let still = last_frame.as_ref() == Some(&frame);
+        if !still {
+            unchanged_since = time::Instant::now();
+        }
+        let frame = last_frame.insert(frame);
+
+        match board_from_image(frame) {
+            Ok((board, y_offset)) => {
                 if board != *prev_board {
-                    return board;
+                    let reading = Reading {
+                        board,
+                        confidence: Box::new(read_confidence(frame, y_offset)),
+                        y_offset,
+                        time: capture_time,
+                    };
+                    return Ok((Observation::NewBoard(reading), failed_reads));
+                }
+                // mostly dark boards of a run look alike, so one is only
+                // taken for a screen once the stack stopped scrolling
+                if still {
+                    if let Some(scene) = scenes.recognize(frame) {
+                        return Ok((scene.into(), failed_reads));
+                    }
+                }
+                if unchanged_since.elapsed() > STALL_TIME {
+                    return Ok((Observation::Frozen, failed_reads));
                 }
+                wait_for_redraw(conn, damage, time::Duration::from_millis(0));
+            }
+            Err(e) => {
+                if let Some(scene) = scenes.recognize(frame) {
+                    return Ok((scene.into(), failed_reads));
+                }
+                failed_reads += 1;
+                if start.elapsed() > STALL_TIME {
+                    return Ok((Observation::NoBoard(e), failed_reads));
+                }
+                wait_for_redraw(conn, damage, RECHECK_WAIT_TIME);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{MAX_COLS, MAX_ROWS};
+    use crate::render;
+
+    // a stand-in for a learned scene, bright bands across a dark board
+    fn title() -> Vec<u8> {
+        let mut image = vec![0; BOARD_IMAGE_BYTES];
+        for y in 0..BOARD_PIXEL_HEIGHT {
+            for x in 0..BOARD_PIXEL_WIDTH {
+                let offset = coord_to_offset(x, y);
+                let level = if (y / 40) % 2 == 0 {
+                    200
+                } else {
+                    (x % 256) as u8
+                };
+                image[offset..offset + 3].copy_from_slice(&[level, level / 2, 255 - level]);
             }
-            None => thread::sleep(RECHECK_WAIT_TIME),
         }
+        image
+    }
+
+    fn scenes() -> Scenes {
+        Scenes {
+            references: vec![(Scene::Title, title())],
+        }
+    }
+
+    #[test]
+    fn a_learned_scene_is_recognized_through_noise() {
+        let mut image = title();
+        for (i, channel) in image.iter_mut().enumerate() {
+            *channel = channel.saturating_add((i % 7) as u8 * 3);
+        }
+        assert_eq!(
+            scenes().recognize(&Frame::native(image)),
+            Some(Scene::Title)
+        );
+    }
+
+    #[test]
+    fn a_board_is_not_taken_for_a_scene() {
+        let items = [[Item::File(Color::Red); MAX_COLS]; MAX_ROWS];
+        let board = board::make_board(3, Item::Empty, items);
+        assert_eq!(
+            scenes().recognize(&Frame::native(render::render(&board))),
+            None
+        );
+    }
+
+    #[test]
+    fn scene_names_parse() {
+        for scene in Scene::ALL.iter() {
+            assert_eq!(scene.name().parse(), Ok(*scene));
+        }
+        assert_eq!("menu".parse::<Scene>(), Err(()));
     }
 }
@@ -0,0 +1,60 @@
+use crate::screen::ITEM_SIZE;
+use std::time::{Duration, Instant};
+
+// weight given to the newest speed measurement
+const SPEED_SMOOTHING: f64 = 0.3;
+// readings closer together than this say more about capture jitter than speed
+const MIN_SAMPLE_GAP: Duration = Duration::from_millis(10);
+
+// follows the stack's offset within a row across readings to estimate how
+// fast it scrolls and when the next row comes in
+#[derive(Default)]
+pub struct Scroll {
+    last: Option<(Instant, usize)>,
+    // pixels per second
+    speed: Option<f64>,
+}
+
+impl Scroll {
+    pub fn update(&mut self, time: Instant, y_offset: usize) {
+        if let Some((last_time, last_offset)) = self.last {
+            let elapsed = time.saturating_duration_since(last_time);
+            if elapsed < MIN_SAMPLE_GAP {
+                return;
+            }
+
+            // readings come far more often than rows, so a smaller offset
+            // means it wrapped around once
+            let moved = (y_offset + ITEM_SIZE - last_offset) % ITEM_SIZE;
+            let speed = moved as f64 / elapsed.as_secs_f64();
+
+            self.speed = Some(match self.speed {
+                Some(old) => old + SPEED_SMOOTHING * (speed - old),
+                None => speed,
+            });
+        }
+
+        self.last = Some((time, y_offset));
+    }
+
+    pub fn speed(&self) -> Option<f64> {
+        self.speed
+    }
+
+    pub fn time_to_next_row(&self) -> Option<Duration> {
+        let (time, offset) = self.last?;
+        let speed = self.speed?;
+        if speed <= 0.0 {
+            return None;
+        }
+
+        // a speed decayed towards zero puts the next row too far off to count
+        let remaining = Duration::try_from_secs_f64((ITEM_SIZE - offset) as f64 / speed).ok()?;
+        Some(remaining.saturating_sub(time.elapsed()))
+    }
+
+    // a new run starts scrolling at its own pace
+    pub fn reset(&mut self) {
+        *self = Scroll::default();
+    }
+}
@@ -0,0 +1,257 @@
+use crate::board::{Board, Move};
+use log::{debug, info, trace, warn};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    // the board already had a match waiting to be realized
+    AlreadyMatched,
+    Match,
+    TimedOut,
+    Exhausted,
+    Cancelled,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::AlreadyMatched => write!(f, "already matched"),
+            Outcome::Match => write!(f, "match"),
+            Outcome::TimedOut => write!(f, "timed out"),
+            Outcome::Exhausted => write!(f, "exhausted"),
+            Outcome::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+// what a search found and how far it looked
+pub struct Search {
+    pub path: Vec<Move>,
+    pub explored: usize,
+    pub depth: usize,
+    pub outcome: Outcome,
+}
+
+// clearing_moves is how many moves can be played before a clearing board's
+// matched items disappear, after that the search continues on the cleared board;
+// incoming is how many rows come in before the path has been played
+pub fn find_match(
+    start: &Board,
+    clearing_moves: usize,
+    incoming: usize,
+    budget: Duration,
+    cancel: &AtomicBool,
+) -> Search {
+    let start = &if start.is_clearing() && clearing_moves == 0 {
+        start.cleared()
+    } else {
+        start.clone()
+    };
+    if start.is_clearing() {
+        debug!(
+            "Board is clearing, planning {} moves before it settles",
+            clearing_moves
+        );
+    }
+
+    if start.has_match() {
+        info!("Board already has an unrealized match");
+        return Search {
+            path: vec![],
+            explored: 0,
+            depth: 0,
+            outcome: Outcome::AlreadyMatched,
+        };
+    }
+
+    let start_time = Instant::now();
+
+    // when a column is about to reach the phage, stop building groups and
+    // take the first match or whatever brings the tall columns down
+    let danger = start.danger(incoming);
+    let emergency = danger.is_emergency();
+    if emergency {
+        warn!(
+            "Emergency: column {} is {} rows from the phage",
+            danger.tallest_col,
+            danger.rows_left()
+        );
+    }
+    let evaluate = |board: &Board| {
+        if emergency {
+            board.emergency_score(incoming)
+        } else {
+            board.score()
+        }
+    };
+
+    let mut boards = VecDeque::with_capacity(10000);
+    let mut seen = HashSet::with_capacity(80000);
+
+    let mut highest_score = evaluate(start);
+    let mut highest_path = vec![];
+
+    let mut explored = 1;
+    let mut steps_ahead = 0;
+
+    seen.insert(start.clone());
+    boards.push_back((start.clone(), vec![]));
+
+    while !boards.is_empty() {
+        if cancel.load(Ordering::Relaxed) {
+            debug!("Search cancelled after exploring {} boards", explored);
+            return Search {
+                path: highest_path,
+                explored,
+                depth: steps_ahead,
+                outcome: Outcome::Cancelled,
+            };
+        }
+
+        if Instant::now().duration_since(start_time) > budget {
+            if highest_path.is_empty() {
+                info!("Search timed out, could not find a match or better board");
+            } else {
+                info!("Search timed out, defaulting to highest score");
+            }
+            debug!(
+                "Explored {} boards, {} moves deep, returning path {} long",
+                explored,
+                steps_ahead,
+                highest_path.len()
+            );
+            return Search {
+                path: highest_path,
+                explored,
+                depth: steps_ahead,
+                outcome: Outcome::TimedOut,
+            };
+        }
+
+        let (board, path) = boards.pop_front().unwrap();
+        if path.len() > steps_ahead {
+            steps_ahead = path.len() + 1;
+        }
+
+        for m in [Move::Left, Move::Right, Move::Swap, Move::Exchange].iter() {
+            let mut new_board = board.do_move(*m);
+            if path.len() + 1 == clearing_moves {
+                new_board = new_board.cleared();
+            }
+            if !seen.contains(&new_board) {
+                explored += 1;
+
+                // Create the path to this board
+                let mut new_path = path.clone();
+                new_path.push(*m);
+
+                // check if the board has a match on it
+                if new_board.has_match() {
+                    info!("Found match");
+                    debug!(
+                        "Explored {} boards, {} moves deep, returning path {} long",
+                        explored,
+                        steps_ahead + 1,
+                        new_path.len()
+                    );
+                    return Search {
+                        path: new_path,
+                        explored,
+                        depth: steps_ahead + 1,
+                        outcome: Outcome::Match,
+                    };
+                }
+
+                // Add the board to the seen list
+                seen.insert(new_board.clone());
+
+                // check if the board has a higher score
+                let new_score = if new_board.is_clearing() {
+                    evaluate(&new_board.cleared())
+                } else {
+                    evaluate(&new_board)
+                };
+                trace!(
+                    "Board {}, {} moves deep, score {:.2}, path {:?}",
+                    explored,
+                    new_path.len(),
+                    new_score,
+                    new_path
+                );
+                if new_score > highest_score {
+                    highest_score = new_score;
+                    highest_path = new_path.clone();
+                }
+
+                // Push the board onto the worklist
+                boards.push_back((new_board, new_path));
+            }
+        }
+    }
+
+    // panic!("find_match() cannot be called on a board with no matches");
+    if highest_path.is_empty() {
+        info!("Exhausted search, could not find a match or better board");
+    } else {
+        info!("Exhausted search, defaulting to highest score");
+    }
+    debug!(
+        "Explored {} boards, {} moves deep, returning path {} long",
+        explored,
+        steps_ahead,
+        highest_path.len()
+    );
+    Search {
+        path: highest_path,
+        explored,
+        depth: steps_ahead,
+        outcome: Outcome::Exhausted,
+    }
+}
+
+// a search running in the background on the board we expect to see once the
+// current path has been played
+pub struct Speculation {
+    pub board: Board,
+    cancel: Arc<AtomicBool>,
+    pub search: thread::JoinHandle<Search>,
+}
+
+impl Speculation {
+    pub fn start(
+        board: Board,
+        clearing_moves: usize,
+        incoming: usize,
+        budget: Duration,
+    ) -> Speculation {
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let search_board = board.clone();
+        let search_cancel = cancel.clone();
+        let search = thread::spawn(move || {
+            find_match(
+                &search_board,
+                clearing_moves,
+                incoming,
+                budget,
+                &search_cancel,
+            )
+        });
+
+        Speculation {
+            board,
+            cancel,
+            search,
+        }
+    }
+
+    // the search thread is left to wind down on its own
+    pub fn discard(self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
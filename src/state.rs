@@ -0,0 +1,100 @@
+use self::GameState::*;
+use crate::screen::Observation;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GameState {
+    Menu,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+impl GameState {
+    // only a learned screen moves to the menu or game over; a run whose board
+    // freezes or disappears without one is taken to be paused, so no keys are
+    // sent until something is recognized again
+    pub fn next(self, observation: &Observation) -> GameState {
+        match observation {
+            Observation::NewBoard(..) => Playing,
+            Observation::Title => Menu,
+            Observation::Pause => Paused,
+            Observation::GameOver => GameOver,
+            Observation::Frozen | Observation::NoBoard(_) => match self {
+                Playing => Paused,
+                state => state,
+            },
+        }
+    }
+
+    pub fn is_over(self) -> bool {
+        self == Menu || self == GameOver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{self, Item, MAX_COLS, MAX_ROWS};
+    use crate::error::Error;
+    use crate::screen::Reading;
+    use std::time::Instant;
+
+    const STATES: [GameState; 4] = [Menu, Playing, Paused, GameOver];
+
+    fn board() -> board::Board {
+        board::make_board(0, Item::Empty, [[Item::Empty; MAX_COLS]; MAX_ROWS])
+    }
+
+    fn new_board() -> Observation {
+        Observation::NewBoard(Reading {
+            board: board(),
+            confidence: Box::new([[1.0; MAX_COLS]; MAX_ROWS]),
+            y_offset: 0,
+            time: Instant::now(),
+        })
+    }
+
+    #[test]
+    fn a_new_board_is_playing() {
+        for state in STATES.iter() {
+            assert_eq!(state.next(&new_board()), Playing);
+        }
+    }
+
+    #[test]
+    fn learned_screens_set_the_state() {
+        for state in STATES.iter() {
+            assert_eq!(state.next(&Observation::Title), Menu);
+            assert_eq!(state.next(&Observation::Pause), Paused);
+            assert_eq!(state.next(&Observation::GameOver), GameOver);
+        }
+    }
+
+    #[test]
+    fn an_unrecognized_stop_pauses_a_run() {
+        assert_eq!(Playing.next(&Observation::Frozen), Paused);
+        assert_eq!(
+            Playing.next(&Observation::NoBoard(Error::Recognition("unreadable"))),
+            Paused
+        );
+    }
+
+    #[test]
+    fn an_unrecognized_stop_keeps_other_states() {
+        for state in [Menu, Paused, GameOver].iter() {
+            assert_eq!(state.next(&Observation::Frozen), *state);
+            assert_eq!(
+                state.next(&Observation::NoBoard(Error::Recognition("unreadable"))),
+                *state
+            );
+        }
+    }
+
+    #[test]
+    fn only_menu_and_game_over_are_over() {
+        assert!(Menu.is_over());
+        assert!(GameOver.is_over());
+        assert!(!Playing.is_over());
+        assert!(!Paused.is_over());
+    }
+}
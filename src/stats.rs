@@ -0,0 +1,167 @@
+use crate::hud::Hud;
+use crate::search::{Outcome, Search};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// totals for one run of the bot, reported when it exits
+pub struct Stats {
+    start: Instant,
+    generations: u64,
+    matches: u64,
+    timeouts: u64,
+    exhausted: u64,
+    already_matched: u64,
+    searches: u64,
+    explored: u64,
+    depth: u64,
+    // frames captured mid run that held no readable board
+    recognition_failures: u64,
+    moves: u64,
+    longest_path: usize,
+    best_score: Option<u64>,
+    capture_time: Duration,
+    // time the main loop waited on searches, speculative ones run alongside
+    // playing and only count for the wait on their result
+    search_time: Duration,
+    input_time: Duration,
+}
+
+fn average(total: u64, count: u64) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total as f64 / count as f64
+    }
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            start: Instant::now(),
+            generations: 0,
+            matches: 0,
+            timeouts: 0,
+            exhausted: 0,
+            already_matched: 0,
+            searches: 0,
+            explored: 0,
+            depth: 0,
+            recognition_failures: 0,
+            moves: 0,
+            longest_path: 0,
+            best_score: None,
+            capture_time: Duration::from_secs(0),
+            search_time: Duration::from_secs(0),
+            input_time: Duration::from_secs(0),
+        }
+    }
+
+    pub fn generations(&self) -> u64 {
+        self.generations
+    }
+
+    pub fn record_search(&mut self, search: &Search, waited: Duration) {
+        match search.outcome {
+            Outcome::Match => self.matches += 1,
+            Outcome::TimedOut => self.timeouts += 1,
+            Outcome::Exhausted => self.exhausted += 1,
+            Outcome::AlreadyMatched => self.already_matched += 1,
+            // a cancelled search is never used
+            Outcome::Cancelled => return,
+        }
+        self.searches += 1;
+        self.explored += search.explored as u64;
+        self.depth += search.depth as u64;
+        self.search_time += waited;
+    }
+
+    pub fn record_path(&mut self, moves: usize, elapsed: Duration) {
+        self.generations += 1;
+        self.moves += moves as u64;
+        self.longest_path = self.longest_path.max(moves);
+        self.input_time += elapsed;
+    }
+
+    pub fn record_hud(&mut self, hud: Hud) {
+        if let Some(score) = hud.score {
+            self.best_score = Some(self.best_score.map_or(score, |best| best.max(score)));
+        }
+    }
+
+    pub fn record_recognition_failures(&mut self, count: usize) {
+        self.recognition_failures += count as u64;
+    }
+
+    pub fn record_captures(&mut self, timings: &[Duration]) {
+        self.capture_time += timings.iter().sum::<Duration>();
+    }
+
+    pub fn json(&self) -> String {
+        let best_score = self
+            .best_score
+            .map_or("null".to_string(), |score| score.to_string());
+        format!(
+            "{{\"duration_s\":{:.3},\"generations\":{},\"matches\":{},\"timeouts\":{},\
+             \"exhausted\":{},\"already_matched\":{},\"average_explored\":{:.1},\
+             \"average_depth\":{:.2},\"recognition_failures\":{},\"moves\":{},\
+             \"average_path_length\":{:.2},\"longest_path\":{},\"best_score\":{},\
+             \"capture_s\":{:.3},\"search_s\":{:.3},\"input_s\":{:.3}}}\n",
+            self.start.elapsed().as_secs_f64(),
+            self.generations,
+            self.matches,
+            self.timeouts,
+            self.exhausted,
+            self.already_matched,
+            average(self.explored, self.searches),
+            average(self.depth, self.searches),
+            self.recognition_failures,
+            self.moves,
+            average(self.moves, self.generations),
+            self.longest_path,
+            best_score,
+            self.capture_time.as_secs_f64(),
+            self.search_time.as_secs_f64(),
+            self.input_time.as_secs_f64()
+        )
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Played {} generations in {:.0} s",
+            self.generations,
+            self.start.elapsed().as_secs_f64()
+        )?;
+        if let Some(score) = self.best_score {
+            writeln!(f, "Best score: {}", score)?;
+        }
+        writeln!(
+            f,
+            "Searches: {} found a match, {} timed out, {} exhausted, {} already matched",
+            self.matches, self.timeouts, self.exhausted, self.already_matched
+        )?;
+        writeln!(
+            f,
+            "Explored {:.1} boards {:.2} moves deep on average",
+            average(self.explored, self.searches),
+            average(self.depth, self.searches)
+        )?;
+        writeln!(
+            f,
+            "Paths: {} moves, {:.2} on average, longest {}",
+            self.moves,
+            average(self.moves, self.generations),
+            self.longest_path
+        )?;
+        writeln!(f, "Recognition failures: {}", self.recognition_failures)?;
+        write!(
+            f,
+            "Time spent: {:.1} s capturing, {:.1} s searching, {:.1} s sending keys",
+            self.capture_time.as_secs_f64(),
+            self.search_time.as_secs_f64(),
+            self.input_time.as_secs_f64()
+        )
+    }
+}
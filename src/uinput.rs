@@ -0,0 +1,111 @@
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::{thread, time};
+
+const UINPUT_PATH: &str = "/dev/uinput";
+const DEVICE_NAME: &[u8] = b"hack_match_bot virtual keyboard";
+
+// ioctl numbers from linux/uinput.h
+const UI_SET_EVBIT: libc::c_ulong = 0x4004_5564;
+const UI_SET_KEYBIT: libc::c_ulong = 0x4004_5565;
+const UI_DEV_SETUP: libc::c_ulong = 0x405c_5503;
+const UI_DEV_CREATE: libc::c_ulong = 0x5501;
+const UI_DEV_DESTROY: libc::c_ulong = 0x5502;
+
+// event types and codes from linux/input-event-codes.h
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const SYN_REPORT: u16 = 0;
+const BUS_VIRTUAL: u16 = 0x06;
+
+// a, d, k, j and enter, in the same order as screen::Keys
+pub const KEY_CODES: [u16; 5] = [30, 32, 37, 36, 28];
+
+// the X server only picks up a new device once udev has announced it
+const DEVICE_SETTLE_TIME: time::Duration = time::Duration::from_millis(500);
+
+// a keyboard made through the kernel, so keys look like they come from real
+// hardware instead of the XTEST extension
+#[derive(Debug)]
+pub struct Keyboard {
+    fd: RawFd,
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+impl Keyboard {
+    pub fn new() -> io::Result<Keyboard> {
+        let path = CString::new(UINPUT_PATH).unwrap();
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // from here on dropping the keyboard closes fd
+        let keyboard = Keyboard { fd };
+
+        unsafe {
+            check(libc::ioctl(fd, UI_SET_EVBIT, libc::c_int::from(EV_KEY)))?;
+            check(libc::ioctl(fd, UI_SET_EVBIT, libc::c_int::from(EV_SYN)))?;
+            for code in KEY_CODES.iter() {
+                check(libc::ioctl(fd, UI_SET_KEYBIT, libc::c_int::from(*code)))?;
+            }
+
+            let mut setup: libc::uinput_setup = mem::zeroed();
+            setup.id.bustype = BUS_VIRTUAL;
+            setup.id.vendor = 0x1234;
+            setup.id.product = 0x5678;
+            for (dst, src) in setup.name.iter_mut().zip(DEVICE_NAME.iter()) {
+                *dst = *src as libc::c_char;
+            }
+            check(libc::ioctl(fd, UI_DEV_SETUP, &setup))?;
+            check(libc::ioctl(fd, UI_DEV_CREATE))?;
+        }
+
+        thread::sleep(DEVICE_SETTLE_TIME);
+        Ok(keyboard)
+    }
+
+    fn emit(&self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+        let mut event: libc::input_event = unsafe { mem::zeroed() };
+        event.type_ = kind;
+        event.code = code;
+        event.value = value;
+
+        let size = mem::size_of::<libc::input_event>();
+        let written = unsafe {
+            libc::write(
+                self.fd,
+                &event as *const libc::input_event as *const libc::c_void,
+                size,
+            )
+        };
+        if written < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    // code is one of KEY_CODES
+    pub fn set_key(&self, code: u16, pressed: bool) -> io::Result<()> {
+        self.emit(EV_KEY, code, pressed as i32)?;
+        self.emit(EV_SYN, SYN_REPORT, 0)
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        unsafe {
+            libc::ioctl(self.fd, UI_DEV_DESTROY);
+            libc::close(self.fd);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::error::{Error, Result};
+use crate::pixel::PixelFormat;
+use crate::screen;
+use log::warn;
+use regex::Regex;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::xproto::*;
+
+const GAME_TITLE: &str = "EXAPUNKS";
+// long enough for any title the game or a window manager sets
+const PROPERTY_LENGTH: u32 = 256;
+const WAIT_POLL_TIME: Duration = Duration::from_millis(1000);
+
+// which window to play in
+pub enum Target {
+    // titled EXAPUNKS or with an EXAPUNKS window class
+    Game,
+    Id(Window),
+    Pid(u32),
+    Title(Regex),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Game => write!(f, "an EXAPUNKS window"),
+            Target::Id(id) => write!(f, "window {:#x}", id),
+            Target::Pid(pid) => write!(f, "a window of process {}", pid),
+            Target::Title(regex) => write!(f, "a window titled like /{}/", regex),
+        }
+    }
+}
+
+// window ids are usually written in hex, as xwininfo and xdotool print them
+pub fn parse_id(s: &str) -> Option<Window> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        Window::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+struct Atoms {
+    net_client_list: Atom,
+    net_wm_name: Atom,
+    net_wm_pid: Atom,
+}
+
+fn intern<Conn: ?Sized + RequestConnection>(conn: &Conn, name: &str) -> Atom {
+    // only_if_exists gives NONE for atoms nobody uses, which no window has
+    intern_atom(conn, true, name.as_bytes())
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map_or(x11rb::NONE, |reply| reply.atom)
+}
+
+fn property<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    window: Window,
+    property: Atom,
+) -> Option<GetPropertyReply> {
+    if property == x11rb::NONE {
+        return None;
+    }
+    let reply = conn
+        .get_property(false, window, property, AtomEnum::ANY, 0, PROPERTY_LENGTH)
+        .ok()?
+        .reply()
+        .ok()?;
+    if reply.format == 0 {
+        None
+    } else {
+        Some(reply)
+    }
+}
+
+fn text_property<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    window: Window,
+    atom: Atom,
+) -> Option<String> {
+    let reply = property(conn, window, atom)?;
+    Some(String::from_utf8_lossy(&reply.value).into_owned())
+}
+
+// _NET_WM_NAME is UTF-8, WM_NAME is whatever encoding the client picked
+fn title<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    atoms: &Atoms,
+    window: Window,
+) -> Option<String> {
+    text_property(conn, window, atoms.net_wm_name)
+        .or_else(|| text_property(conn, window, AtomEnum::WM_NAME.into()))
+}
+
+// WM_CLASS holds the instance and class names, each ending in a nul
+fn class<Conn: ?Sized + RequestConnection>(conn: &Conn, window: Window) -> Vec<String> {
+    text_property(conn, window, AtomEnum::WM_CLASS.into())
+        .map(|class| {
+            class
+                .split('\0')
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn pid<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    atoms: &Atoms,
+    window: Window,
+) -> Option<u32> {
+    property(conn, window, atoms.net_wm_pid)?.value32()?.next()
+}
+
+fn matches<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    atoms: &Atoms,
+    target: &Target,
+    window: Window,
+) -> bool {
+    match target {
+        Target::Game => {
+            title(conn, atoms, window).is_some_and(|title| title == GAME_TITLE)
+                || class(conn, window)
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(GAME_TITLE))
+        }
+        Target::Id(id) => window == *id,
+        Target::Pid(target_pid) => pid(conn, atoms, window) == Some(*target_pid),
+        Target::Title(regex) => title(conn, atoms, window).is_some_and(|t| regex.is_match(&t)),
+    }
+}
+
+fn all_windows<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    window: Window,
+    windows: &mut Vec<Window>,
+) {
+    windows.push(window);
+    if let Some(reply) = conn.query_tree(window).ok().and_then(|c| c.reply().ok()) {
+        for child in reply.children {
+            all_windows(conn, child, windows);
+        }
+    }
+}
+
+// the window manager's list of client windows when there is one, otherwise
+// every window under root
+fn candidates<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    atoms: &Atoms,
+    root: Window,
+) -> Vec<Window> {
+    let clients: Vec<Window> = property(conn, root, atoms.net_client_list)
+        .and_then(|reply| reply.value32().map(|windows| windows.collect()))
+        .unwrap_or_default();
+    if !clients.is_empty() {
+        return clients;
+    }
+
+    let mut windows = Vec::new();
+    all_windows(conn, root, &mut windows);
+    windows
+}
+
+pub fn find<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    root: Window,
+    target: &Target,
+) -> Result<Window> {
+    if let Target::Id(id) = target {
+        // a given id does not need to be a managed client
+        return match conn.get_geometry(*id).ok().and_then(|c| c.reply().ok()) {
+            Some(_) => Ok(*id),
+            None => Err(Error::WindowNotFound(format!(
+                "No window with id {:#x}",
+                id
+            ))),
+        };
+    }
+
+    let atoms = Atoms {
+        net_client_list: intern(conn, "_NET_CLIENT_LIST"),
+        net_wm_name: intern(conn, "_NET_WM_NAME"),
+        net_wm_pid: intern(conn, "_NET_WM_PID"),
+    };
+
+    let found: Vec<Window> = candidates(conn, &atoms, root)
+        .into_iter()
+        .filter(|window| matches(conn, &atoms, target, *window))
+        .collect();
+
+    match found.first() {
+        Some(window) => {
+            if found.len() > 1 {
+                warn!(
+                    "{} windows match, using {:#x}; pick one with --window-id",
+                    found.len(),
+                    window
+                );
+            }
+            Ok(*window)
+        }
+        None => Err(Error::WindowNotFound(format!("Unable to find {}", target))),
+    }
+}
+
+// looks for the target every WAIT_POLL_TIME until it is there and passes
+// validation again, problem being what made it unusable
+pub fn wait_until_usable<Conn: ?Sized + RequestConnection>(
+    conn: &Conn,
+    setup: &Setup,
+    screen: &Screen,
+    target: &Target,
+    problem: &Error,
+) -> Result<(Window, PixelFormat)> {
+    let mut last_problem = problem.to_string();
+
+    loop {
+        let found = find(conn, screen.root, target).and_then(|window| {
+            let format = screen::validate_window(conn, setup, screen, window)?;
+            Ok((window, format))
+        });
+
+        match found {
+            Ok(found) => return Ok(found),
+            Err(e) if e.is_window_problem() => {
+                // only say something when the reason changes
+                let problem = e.to_string();
+                if problem != last_problem {
+                    warn!("{}", problem);
+                    last_problem = problem;
+                }
+            }
+            Err(e) => return Err(e),
+        }
+
+        thread::sleep(WAIT_POLL_TIME);
+    }
+}
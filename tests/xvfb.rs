@@ -0,0 +1,101 @@
+// plays the fake game on a private Xvfb server, going through window
+// discovery, validation, capture, recognition and XTEST input like a real run;
+// needs Xvfb installed, so it only runs with cargo test -- --ignored
+
+use std::env;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const BOT: &str = env!("CARGO_BIN_EXE_hack_match_bot");
+const SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+const MATCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+// kills the process when the test ends, passed or not
+struct Running(Child);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn start_xvfb() -> (Running, String) {
+    let number = 100 + std::process::id() % 1000;
+    let display = format!(":{}", number);
+
+    let server = Command::new("Xvfb")
+        .args([&display, "-screen", "0", "1920x1080x24", "-nolisten", "tcp"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Unable to start Xvfb, install it to run this test");
+    let server = Running(server);
+
+    let socket = format!("/tmp/.X11-unix/X{}", number);
+    let start = Instant::now();
+    while !Path::new(&socket).exists() {
+        if start.elapsed() > SERVER_TIMEOUT {
+            panic!("Xvfb did not start on {}", display);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    (server, display)
+}
+
+#[test]
+#[ignore]
+fn bot_clears_a_match_in_the_fake_game() {
+    let (_server, display) = start_xvfb();
+
+    let mut game = Command::new(BOT)
+        .arg("--fake-game")
+        .env("DISPLAY", &display)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Unable to start the fake game");
+    let stdout = game.stdout.take().unwrap();
+    let _game = Running(game);
+
+    let (lines, received) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if lines.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let first = received
+        .recv_timeout(SERVER_TIMEOUT)
+        .expect("The fake game did not open its window");
+    assert!(first.starts_with("Fake game window"), "{}", first);
+
+    let profile = env::temp_dir().join(format!("hack_match_bot_test_{}", std::process::id()));
+    let _bot = Running(
+        Command::new(BOT)
+            .arg("--profile")
+            .arg(&profile)
+            .env("DISPLAY", &display)
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("Unable to start the bot"),
+    );
+
+    let start = Instant::now();
+    loop {
+        let remaining = MATCH_TIMEOUT
+            .checked_sub(start.elapsed())
+            .expect("The bot did not clear a match");
+        match received.recv_timeout(remaining) {
+            Ok(line) if line == "Cleared" => break,
+            Ok(_) => (),
+            Err(_) => panic!("The bot did not clear a match"),
+        }
+    }
+}